@@ -0,0 +1,59 @@
+use crate::datastructs::BalloonState;
+use std::time::{Duration, Instant};
+
+// the driver may stop a few pages short of the target
+const BALLOON_SLACK_KB: usize = 1024;
+// time without progress before we consider it stuck
+const BALLOON_STUCK_AFTER: Duration = Duration::from_secs(30);
+
+// Follows the progress of the balloon driver across samples
+#[derive(Default)]
+pub struct BalloonProgress {
+    last_current_kb: Option<usize>,
+    // last time the driver moved or was on target
+    last_progress: Option<Instant>,
+}
+
+impl BalloonProgress {
+    pub fn new() -> BalloonProgress {
+        BalloonProgress::default()
+    }
+
+    pub fn update(&mut self, current_kb: usize, target_kb: usize, now: Instant) -> BalloonState {
+        let reached = current_kb.abs_diff(target_kb) <= BALLOON_SLACK_KB;
+        let moved = self.last_current_kb != Some(current_kb);
+        self.last_current_kb = Some(current_kb);
+        if reached || moved {
+            self.last_progress = Some(now);
+        }
+        if reached {
+            BalloonState::Reached
+        } else if self.last_progress.is_some_and(|last| now - last >= BALLOON_STUCK_AFTER) {
+            BalloonState::Stuck
+        } else {
+            BalloonState::Moving
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut progress = BalloonProgress::new();
+        assert_eq!(progress.update(4194304, 4194304, at(0)), BalloonState::Reached);
+        // new target
+        assert_eq!(progress.update(4194304, 2097152, at(10)), BalloonState::Moving);
+        assert_eq!(progress.update(3145728, 2097152, at(20)), BalloonState::Moving);
+        assert_eq!(progress.update(3145728, 2097152, at(49)), BalloonState::Moving);
+        assert_eq!(progress.update(3145728, 2097152, at(50)), BalloonState::Stuck);
+        assert_eq!(progress.update(3145728, 2097152, at(500)), BalloonState::Stuck);
+        // moving again
+        assert_eq!(progress.update(3000000, 2097152, at(510)), BalloonState::Moving);
+        assert_eq!(progress.update(2097152 + 512, 2097152, at(520)), BalloonState::Reached);
+    }
+}
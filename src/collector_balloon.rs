@@ -0,0 +1,14 @@
+use crate::datastructs::BalloonInfo;
+use std::io;
+
+pub struct BalloonSource {}
+
+impl BalloonSource {
+    pub fn new() -> io::Result<BalloonSource> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for balloon"))
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<BalloonInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for balloon"))
+    }
+}
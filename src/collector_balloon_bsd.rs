@@ -0,0 +1,31 @@
+use crate::balloon_progress::BalloonProgress;
+use crate::collector_memory::{get_field_number, new_sysctl};
+use crate::datastructs::BalloonInfo;
+use std::io;
+use std::time::Instant;
+
+pub struct BalloonSource {
+    // those are in pages
+    current_ctl: sysctl::Ctl,
+    target_ctl: sysctl::Ctl,
+    pagesize_kb: usize,
+    progress: BalloonProgress,
+}
+
+impl BalloonSource {
+    // fails with NotFound when there is no balloon driver
+    pub fn new() -> io::Result<BalloonSource> {
+        let current_ctl = new_sysctl("dev.xen.balloon.current")?;
+        let target_ctl = new_sysctl("dev.xen.balloon.target")?;
+        let pagesize = get_field_number(&new_sysctl("hw.pagesize")?)? as usize;
+        Ok(BalloonSource { current_ctl, target_ctl, pagesize_kb: pagesize / 1024,
+                           progress: BalloonProgress::new() })
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<BalloonInfo> {
+        let current_kb = get_field_number(&self.current_ctl)? as usize * self.pagesize_kb;
+        let target_kb = get_field_number(&self.target_ctl)? as usize * self.pagesize_kb;
+        Ok(BalloonInfo { current_kb, target_kb,
+                         state: self.progress.update(current_kb, target_kb, Instant::now()) })
+    }
+}
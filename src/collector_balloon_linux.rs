@@ -0,0 +1,33 @@
+use crate::balloon_progress::BalloonProgress;
+use crate::datastructs::BalloonInfo;
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+const XEN_MEMORY_DIR: &str = "/sys/devices/system/xen_memory/xen_memory0";
+
+pub struct BalloonSource {
+    progress: BalloonProgress,
+}
+
+impl BalloonSource {
+    // fails with NotFound when there is no balloon driver
+    pub fn new() -> io::Result<BalloonSource> {
+        fs::metadata(XEN_MEMORY_DIR)
+            .map_err(|e| io::Error::new(e.kind(), format!("{XEN_MEMORY_DIR}: {e}")))?;
+        Ok(BalloonSource { progress: BalloonProgress::new() })
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<BalloonInfo> {
+        let current_kb = read_kb(&format!("{XEN_MEMORY_DIR}/info/current_kb"))?;
+        let target_kb = read_kb(&format!("{XEN_MEMORY_DIR}/target_kb"))?;
+        Ok(BalloonInfo { current_kb, target_kb,
+                         state: self.progress.update(current_kb, target_kb, Instant::now()) })
+    }
+}
+
+fn read_kb(path: &str) -> io::Result<usize> {
+    let value = fs::read_to_string(path)?;
+    value.trim().parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("parsing {path}: {e}")))
+}
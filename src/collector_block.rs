@@ -0,0 +1,14 @@
+use crate::datastructs::BlockInfo;
+use std::io;
+
+pub struct BlockStatsSource {}
+
+impl BlockStatsSource {
+    pub fn new() -> io::Result<BlockStatsSource> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for block stats"))
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<BlockInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for block stats"))
+    }
+}
@@ -0,0 +1,112 @@
+use crate::collector_memory::{get_field_number, new_sysctl};
+use crate::datastructs::{BlockInfo, BlockStats};
+use crate::vbd_detect;
+use std::ffi::CStr;
+use std::io;
+use std::mem;
+use std::ptr;
+
+// from sys/devicestat.h, what kern.devstat.all returns after a
+// generation number
+const DEVSTAT_VERSION: u64 = 6;
+const DEVSTAT_NAME_LEN: usize = 16;
+const DEVSTAT_N_TRANS_FLAGS: usize = 4;
+const DEVSTAT_READ: usize = 1;
+const DEVSTAT_WRITE: usize = 2;
+const DEVSTAT_TYPE_MASK: libc::c_int = 0x0f;
+const DEVSTAT_TYPE_DIRECT: libc::c_int = 0x00;
+const DEVSTAT_TYPE_PASS: libc::c_int = 0x100;
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Devstat {
+    sequence0: libc::c_uint,
+    allocated: libc::c_int,
+    start_count: libc::c_uint,
+    end_count: libc::c_uint,
+    busy_from: libc::bintime,
+    dev_links: *mut Devstat,
+    device_number: u32,
+    device_name: [libc::c_char; DEVSTAT_NAME_LEN],
+    unit_number: libc::c_int,
+    bytes: [u64; DEVSTAT_N_TRANS_FLAGS],
+    operations: [u64; DEVSTAT_N_TRANS_FLAGS],
+    duration: [libc::bintime; DEVSTAT_N_TRANS_FLAGS],
+    busy_time: libc::bintime,
+    creation_time: libc::bintime,
+    block_size: u32,
+    tag_types: [u64; 3],
+    flags: libc::c_int,
+    device_type: libc::c_int,
+    priority: libc::c_int,
+    id: *const libc::c_void,
+    sequence1: libc::c_uint,
+}
+
+pub struct BlockStatsSource {}
+
+impl BlockStatsSource {
+    pub fn new() -> io::Result<BlockStatsSource> {
+        let version = get_field_number(&new_sysctl("kern.devstat.version")?)?;
+        if version != DEVSTAT_VERSION {
+            return Err(io::Error::new(io::ErrorKind::Unsupported,
+                                      format!("unknown devstat version {version}")));
+        }
+        Ok(BlockStatsSource {})
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<BlockInfo> {
+        let buf = read_devstat_all()?;
+        let disks = buf[mem::size_of::<libc::c_long>()..]
+            .chunks_exact(mem::size_of::<Devstat>())
+            .map(|chunk| unsafe { ptr::read_unaligned(chunk.as_ptr() as *const Devstat) })
+            // disks only, no CD or pass(4) devices
+            .filter(|devstat| devstat.device_type & DEVSTAT_TYPE_MASK == DEVSTAT_TYPE_DIRECT
+                    && devstat.device_type & DEVSTAT_TYPE_PASS == 0)
+            .map(|devstat| block_stats(&devstat))
+            .collect();
+        Ok(BlockInfo { disks })
+    }
+}
+
+fn read_devstat_all() -> io::Result<Vec<u8>> {
+    let name = b"kern.devstat.all\0".as_ptr() as *const libc::c_char;
+    let mut len = 0;
+    if unsafe { libc::sysctlbyname(name, ptr::null_mut(), &mut len, ptr::null(), 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // room for devices attached meanwhile
+    len += 4 * mem::size_of::<Devstat>();
+    let mut buf = vec![0u8; len];
+    if unsafe { libc::sysctlbyname(name, buf.as_mut_ptr() as *mut libc::c_void, &mut len,
+                                   ptr::null(), 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buf.truncate(len);
+    if buf.len() < mem::size_of::<libc::c_long>() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "short kern.devstat.all"));
+    }
+    Ok(buf)
+}
+
+fn block_stats(devstat: &Devstat) -> BlockStats {
+    let driver = unsafe { CStr::from_ptr(devstat.device_name.as_ptr()) }.to_string_lossy();
+    let name = format!("{driver}{}", devstat.unit_number);
+    // time spent by completed requests, there is no weighted queue
+    // time like on Linux
+    let queue_ms = devstat.duration.iter().map(bintime_ms).sum();
+    BlockStats {
+        vbd: vbd_detect::get_vbd_id(&name),
+        name,
+        read_ops: devstat.operations[DEVSTAT_READ],
+        read_bytes: devstat.bytes[DEVSTAT_READ],
+        write_ops: devstat.operations[DEVSTAT_WRITE],
+        write_bytes: devstat.bytes[DEVSTAT_WRITE],
+        in_flight: devstat.start_count.wrapping_sub(devstat.end_count) as u64,
+        busy_ms: bintime_ms(&devstat.busy_time),
+        queue_ms,
+    }
+}
+
+fn bintime_ms(bintime: &libc::bintime) -> u64 {
+    bintime.sec as u64 * 1000 + (((bintime.frac >> 32) * 1000) >> 32)
+}
@@ -0,0 +1,90 @@
+use crate::datastructs::{BlockInfo, BlockStats};
+use crate::vbd_detect;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const PROC_DISKSTATS: &str = "/proc/diskstats";
+// diskstats counts sectors of 512 bytes, whatever the device
+const SECTOR_SIZE: u64 = 512;
+
+pub struct BlockStatsSource {}
+
+impl BlockStatsSource {
+    pub fn new() -> io::Result<BlockStatsSource> {
+        Ok(BlockStatsSource {})
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<BlockInfo> {
+        let diskstats = fs::read_to_string(PROC_DISKSTATS)
+            .map_err(|e| io::Error::new(e.kind(), format!("{PROC_DISKSTATS}: {e}")))?;
+        let disks = diskstats.lines()
+            .filter_map(parse_diskstats_line)
+            // only disks backed by a device, no partitions, loop,
+            // zram or device-mapper
+            .filter(|disk| Path::new(&format!("/sys/block/{}/device", disk.name)).exists())
+            .map(|mut disk| {
+                disk.vbd = vbd_detect::get_vbd_id(&disk.name);
+                disk
+            })
+            .collect();
+        Ok(BlockInfo { disks })
+    }
+}
+
+// "202 0 xvda 1000 0 2000 300 ..." see Documentation/admin-guide/iostats.rst
+fn parse_diskstats_line(line: &str) -> Option<BlockStats> {
+    let mut fields = line.split_whitespace();
+    let name = fields.nth(2)?.to_string();
+    let counters = fields.map(|field| field.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if counters.len() < 11 {
+        log::debug!("ignoring short diskstats line {line:?}");
+        return None;
+    }
+    Some(BlockStats {
+        name,
+        vbd: None,
+        read_ops: counters[0],
+        read_bytes: counters[2] * SECTOR_SIZE,
+        write_ops: counters[4],
+        write_bytes: counters[6] * SECTOR_SIZE,
+        in_flight: counters[8],
+        busy_ms: counters[9],
+        queue_ms: counters[10],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diskstats_lines() {
+        // 11 counters, before Linux 4.18
+        let disk = parse_diskstats_line(" 202       0 xvda 1000 10 2000 300 400 20 800 500 2 700 900")
+            .unwrap();
+        assert_eq!(disk, BlockStats { name: "xvda".to_string(), vbd: None,
+                                      read_ops: 1000, read_bytes: 2000 * 512,
+                                      write_ops: 400, write_bytes: 800 * 512,
+                                      in_flight: 2, busy_ms: 700, queue_ms: 900 });
+        // discard (4.18+) and flush (5.5+) counters are ignored
+        let disk = parse_diskstats_line(" 259       0 nvme0n1 1000 10 2000 300 400 20 800 500 \
+                                         2 700 900 5 0 40 6 7 8")
+            .unwrap();
+        assert_eq!((disk.read_ops, disk.write_bytes, disk.queue_ms), (1000, 800 * 512, 900));
+    }
+
+    #[test]
+    fn bad_diskstats_lines() {
+        // partitions of Linux < 2.6.25 only had 4 counters
+        assert_eq!(parse_diskstats_line("   3    1 hda1 35486 38030 38030 38030"), None);
+        assert_eq!(parse_diskstats_line(" 202 0 xvda"), None);
+        assert_eq!(parse_diskstats_line(" 202 0"), None);
+        assert_eq!(parse_diskstats_line(""), None);
+        assert_eq!(parse_diskstats_line(" 202 0 xvda 1000 10 2000 300 400 20 x 500 2 700 900"),
+                   None);
+        assert_eq!(parse_diskstats_line(" 202 0 xvda 1000 10 2000 300 400 20 -8 500 2 700 900"),
+                   None);
+    }
+}
@@ -0,0 +1,19 @@
+use crate::datastructs::DnsInfo;
+use futures::stream::Stream;
+use std::io;
+
+pub struct DnsSource {}
+
+impl DnsSource {
+    pub fn new() -> io::Result<DnsSource> {
+        Ok(DnsSource {})
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<DnsInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for dns"))
+    }
+
+    pub fn stream(&mut self) -> impl Stream<Item = io::Result<DnsInfo>> + '_ {
+        futures::stream::pending::<io::Result<DnsInfo>>()
+    }
+}
@@ -0,0 +1,189 @@
+use crate::datastructs::DnsInfo;
+use async_stream::try_stream;
+use futures::stream::{Stream, StreamExt};
+use inotify::{Inotify, WatchMask, Watches};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+// when /etc/resolv.conf only points to the systemd-resolved stub, the
+// servers it really uses are listed here
+const RESOLVED_UPSTREAM_CONF: &str = "/run/systemd/resolve/resolv.conf";
+const RESOLVED_STUB_ADDRESS: &str = "127.0.0.53";
+
+pub struct DnsSource {
+    // last config we returned, to only report actual changes
+    current: Option<DnsInfo>,
+}
+
+impl DnsSource {
+    pub fn new() -> io::Result<DnsSource> {
+        Ok(DnsSource { current: None })
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<DnsInfo> {
+        let dns_info = read_dns_info()?;
+        self.current = Some(dns_info.clone());
+        Ok(dns_info)
+    }
+
+    pub fn stream(&mut self) -> impl Stream<Item = io::Result<DnsInfo>> + '_ {
+        try_stream! {
+            let inotify = Inotify::init()?;
+            let mut watches = inotify.watches();
+            let mut watched = vec![];
+            add_watches(&mut watches, &mut watched);
+            let mut events = inotify.into_event_stream([0u8; 1024])?;
+            while let Some(event) = events.next().await {
+                let event = event?;
+                // e.g. systemd-resolved started, creating its directory
+                let new_watches = add_watches(&mut watches, &mut watched);
+                match event.name {
+                    Some(name) if name.to_string_lossy().ends_with("resolv.conf") => (),
+                    // the file may have been written before we watched
+                    _ if new_watches => (),
+                    _ => continue,
+                }
+                let dns_info = match read_dns_info() {
+                    Ok(dns_info) => dns_info,
+                    Err(e) => {
+                        log::warn!("cannot read resolver config: {e}");
+                        continue;
+                    },
+                };
+                if self.current.as_ref() != Some(&dns_info) {
+                    log::debug!("resolver config changed: {dns_info:?}");
+                    self.current = Some(dns_info.clone());
+                    yield dns_info;
+                }
+            }
+        }
+    }
+}
+
+// Watch the directories that may hold resolver config and are not
+// watched yet, telling whether there were any
+fn add_watches(watches: &mut Watches, watched: &mut Vec<PathBuf>) -> bool {
+    let mut added = false;
+    for dir in watched_dirs() {
+        if watched.contains(&dir) {
+            continue;
+        }
+        log::debug!("watching {dir:?} for resolver changes");
+        match watches.add(&dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO
+                          | WatchMask::CREATE | WatchMask::DELETE) {
+            Ok(_) => {
+                watched.push(dir);
+                added = true;
+            },
+            Err(e) => log::warn!("cannot watch {dir:?} for resolver changes: {e}"),
+        }
+    }
+    added
+}
+
+// resolv.conf is usually replaced rather than modified in place, so we
+// watch the directories holding it.  Those not existing yet get
+// replaced with their closest existing ancestor, to notice their
+// creation.
+fn watched_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/etc")];
+    // /etc/resolv.conf is often a symlink to a file managed by a
+    // network daemon, possibly not running yet
+    let target = fs::canonicalize(RESOLV_CONF).or_else(|_| {
+        fs::read_link(RESOLV_CONF).map(|target| Path::new(RESOLV_CONF).with_file_name(target))
+    });
+    if let Some(dir) = target.ok().as_deref().and_then(Path::parent) {
+        dirs.push(dir.to_path_buf());
+    }
+    if let Some(dir) = Path::new(RESOLVED_UPSTREAM_CONF).parent() {
+        dirs.push(dir.to_path_buf());
+    }
+    let mut dirs: Vec<PathBuf> = dirs.iter()
+        .filter_map(|dir| dir.ancestors().find(|dir| dir.is_dir()))
+        .map(Path::to_path_buf)
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+fn read_dns_info() -> io::Result<DnsInfo> {
+    let dns_info = match fs::read_to_string(RESOLV_CONF) {
+        Ok(content) => parse_resolv_conf(&content),
+        // no resolver configured, or file being replaced
+        Err(e) if e.kind() == io::ErrorKind::NotFound => DnsInfo::default(),
+        Err(e) => return Err(e),
+    };
+    if dns_info.nameservers == [RESOLVED_STUB_ADDRESS] {
+        match fs::read_to_string(RESOLVED_UPSTREAM_CONF) {
+            Ok(content) => return Ok(parse_resolv_conf(&content)),
+            Err(e) => log::debug!("cannot read {RESOLVED_UPSTREAM_CONF}: {e}"),
+        }
+    }
+    Ok(dns_info)
+}
+
+// see resolv.conf(5)
+fn parse_resolv_conf(content: &str) -> DnsInfo {
+    let mut dns_info = DnsInfo::default();
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("nameserver") => dns_info.nameservers.extend(words.next().map(str::to_string)),
+            // "domain" and "search" are mutually exclusive, last one wins
+            Some("domain") => dns_info.search = words.next().map(str::to_string).into_iter().collect(),
+            Some("search") => dns_info.search = words.map(str::to_string).collect(),
+            _ => (),
+        }
+    }
+    dns_info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_and_domain() {
+        let dns_info = parse_resolv_conf("search example.com corp.example.com\n");
+        assert_eq!(dns_info.search, ["example.com", "corp.example.com"]);
+        // mutually exclusive, last one wins
+        let dns_info = parse_resolv_conf("search example.com corp.example.com\n\
+                                          domain example.org\n");
+        assert_eq!(dns_info.search, ["example.org"]);
+        let dns_info = parse_resolv_conf("domain example.org\nsearch a.example b.example\n");
+        assert_eq!(dns_info.search, ["a.example", "b.example"]);
+    }
+
+    #[test]
+    fn options_and_comments() {
+        let dns_info = parse_resolv_conf("# Generated by NetworkManager\n\
+                                          ; nameserver 192.0.2.99\n\
+                                          #nameserver 192.0.2.98\n\
+                                          nameserver 192.0.2.1\n\
+                                          options edns0 trust-ad timeout:2\n\
+                                          sortlist 130.155.160.0/255.255.240.0\n\
+                                          \n\
+                                          nameserver   192.0.2.2   \n");
+        assert_eq!(dns_info, DnsInfo { nameservers: vec!["192.0.2.1".to_string(),
+                                                         "192.0.2.2".to_string()],
+                                       search: vec![] });
+    }
+
+    #[test]
+    fn ipv6_nameservers() {
+        let dns_info = parse_resolv_conf("nameserver 2001:db8::53\n\
+                                          nameserver fe80::1%eth0\n\
+                                          nameserver fe80::2%2\n");
+        assert_eq!(dns_info.nameservers, ["2001:db8::53", "fe80::1%eth0", "fe80::2%2"]);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(parse_resolv_conf(""), DnsInfo::default());
+        // keywords without values
+        assert_eq!(parse_resolv_conf("nameserver\nsearch\n"), DnsInfo::default());
+    }
+}
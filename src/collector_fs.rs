@@ -0,0 +1,14 @@
+use crate::datastructs::FsInfo;
+use std::io;
+
+pub struct FilesystemSource {}
+
+impl FilesystemSource {
+    pub fn new() -> io::Result<FilesystemSource> {
+        Ok(FilesystemSource {})
+    }
+
+    pub async fn collect_current(&mut self) -> io::Result<FsInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for filesystems"))
+    }
+}
@@ -0,0 +1,56 @@
+use crate::datastructs::{FsInfo, FsUsage};
+use crate::vbd_detect;
+use std::ffi::CStr;
+use std::io;
+use std::ptr;
+use std::slice;
+
+// flagged local, but nothing the admin would want disk metrics for
+const PSEUDO_FSTYPES: [&str; 8] = ["devfs", "fdescfs", "procfs", "linprocfs", "linsysfs",
+                                   "tmpfs", "nullfs", "mqueuefs"];
+
+pub struct FilesystemSource {}
+
+impl FilesystemSource {
+    pub fn new() -> io::Result<FilesystemSource> {
+        Ok(FilesystemSource {})
+    }
+
+    pub async fn collect_current(&mut self) -> io::Result<FsInfo> {
+        let mut mntbuf: *mut libc::statfs = ptr::null_mut();
+        // the buffer is owned by libc and reused by the next call
+        let count = unsafe { libc::getmntinfo(&mut mntbuf, libc::MNT_NOWAIT) };
+        if count <= 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mounts = unsafe { slice::from_raw_parts(mntbuf, count as usize) };
+        let filesystems = mounts.iter()
+            .filter(|stat| stat.f_flags & libc::MNT_LOCAL != 0
+                    && !PSEUDO_FSTYPES.contains(&field(&stat.f_fstypename).as_str()))
+            .map(fs_usage)
+            .collect();
+        Ok(FsInfo { filesystems })
+    }
+}
+
+fn fs_usage(stat: &libc::statfs) -> FsUsage {
+    let kb = |blocks: u64| blocks * stat.f_bsize / 1024;
+    let device = field(&stat.f_mntfromname);
+    FsUsage {
+        mountpoint: field(&stat.f_mntonname),
+        vbd: vbd_detect::get_vbd_id(&device),
+        device,
+        fstype: field(&stat.f_fstypename),
+        size_kb: kb(stat.f_blocks),
+        used_kb: kb(stat.f_blocks - stat.f_bfree),
+        // negative when root dipped into the reserve
+        available_kb: kb(stat.f_bavail.max(0) as u64),
+        inodes_total: stat.f_files,
+        inodes_free: stat.f_ffree.max(0) as u64,
+    }
+}
+
+fn field(chars: &[libc::c_char]) -> String {
+    // NUL-terminated by the kernel
+    unsafe { CStr::from_ptr(chars.as_ptr()) }.to_string_lossy().into_owned()
+}
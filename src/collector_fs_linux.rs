@@ -0,0 +1,204 @@
+use crate::datastructs::{FsInfo, FsUsage};
+use crate::vbd_detect;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+pub const PROC_MOUNTINFO: &str = "/proc/self/mountinfo";
+// local filesystems not backed by a block device
+const NODEV_LOCAL_FSTYPES: [&str; 2] = ["zfs", "bcachefs"];
+// statvfs() does not return while the device does not answer
+const STATVFS_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct FilesystemSource {
+    // statvfs() calls that timed out, by mountpoint, not retried
+    // until they return
+    stalled: Vec<(String, JoinHandle<io::Result<libc::statvfs>>)>,
+}
+
+impl FilesystemSource {
+    pub fn new() -> io::Result<FilesystemSource> {
+        Ok(FilesystemSource { stalled: vec![] })
+    }
+
+    pub async fn collect_current(&mut self) -> io::Result<FsInfo> {
+        let mountinfo = read_mountinfo()?;
+        self.stalled.retain(|(_, task)| !task.is_finished());
+        let mut filesystems = vec![];
+        for mount in local_filesystems(&mountinfo) {
+            if self.stalled.iter().any(|(mountpoint, _)| *mountpoint == mount.mountpoint) {
+                log::debug!("statvfs({}) still stalled", mount.mountpoint);
+                continue;
+            }
+            let mountpoint = mount.mountpoint.clone();
+            let mut task = tokio::task::spawn_blocking(move || statvfs(&mountpoint));
+            match tokio::time::timeout(STATVFS_TIMEOUT, &mut task).await {
+                Ok(Ok(Ok(stat))) => filesystems.push(fs_usage(&mount, &stat)),
+                // e.g. unmounted meanwhile
+                Ok(Ok(Err(e))) => log::debug!("statvfs({}): {e}", mount.mountpoint),
+                Ok(Err(e)) => log::error!("statvfs({}) did not complete: {e}", mount.mountpoint),
+                Err(_) => {
+                    log::warn!("statvfs({}) stalled, skipping it until it returns",
+                               mount.mountpoint);
+                    self.stalled.push((mount.mountpoint.clone(), task));
+                },
+            }
+        }
+        Ok(FsInfo { filesystems })
+    }
+}
+
+// bind mounts and btrfs subvolumes show the same filesystem again,
+// only its first mount is kept
+fn local_filesystems(mountinfo: &str) -> Vec<Mount<'_>> {
+    let mut filesystems: Vec<Mount> = vec![];
+    for mount in mountinfo.lines().filter_map(parse_mountinfo_line) {
+        if !mount.is_local() {
+            continue;
+        }
+        if filesystems.iter().any(|other| other.device_id == mount.device_id) {
+            continue;
+        }
+        filesystems.push(mount);
+    }
+    filesystems
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Mount<'a> {
+    pub device_id: &'a str, // "major:minor"
+    pub mountpoint: String,
+    pub options: &'a str,
+    pub fstype: &'a str,
+    pub source: String,
+    pub super_options: &'a str,
+}
+
+impl Mount<'_> {
+    // backed by a block device, or a known local filesystem; not e.g.
+    // "//server/share" (cifs) or "server:/export" (nfs)
+    pub fn is_local(&self) -> bool {
+        self.source.starts_with("/dev/") || NODEV_LOCAL_FSTYPES.contains(&self.fstype)
+    }
+}
+
+pub fn read_mountinfo() -> io::Result<String> {
+    fs::read_to_string(PROC_MOUNTINFO)
+        .map_err(|e| io::Error::new(e.kind(), format!("{PROC_MOUNTINFO}: {e}")))
+}
+
+// "36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue"
+pub fn parse_mountinfo_line(line: &str) -> Option<Mount<'_>> {
+    let (mount_fields, fs_fields) = line.split_once(" - ")?;
+    let mut mount_fields = mount_fields.split(' ');
+    let device_id = mount_fields.nth(2)?;
+    let mountpoint = unescape(mount_fields.nth(1)?);
+    let options = mount_fields.next()?;
+    let mut fs_fields = fs_fields.split(' ');
+    let fstype = fs_fields.next()?;
+    let source = unescape(fs_fields.next()?);
+    let super_options = fs_fields.next()?;
+    Some(Mount { device_id, mountpoint, options, fstype, source, super_options })
+}
+
+// the kernel escapes space, tab, newline and backslash as octal
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        unescaped.push_str(&rest[..pos]);
+        match rest.get(pos + 1..pos + 4).and_then(|octal| u8::from_str_radix(octal, 8).ok()) {
+            Some(c) => {
+                unescaped.push(c as char);
+                rest = &rest[pos + 4..];
+            },
+            None => {
+                unescaped.push('\\');
+                rest = &rest[pos + 1..];
+            },
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn statvfs(path: &str) -> io::Result<libc::statvfs> {
+    let c_path = CString::new(path)?;
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat)
+}
+
+fn fs_usage(mount: &Mount, stat: &libc::statvfs) -> FsUsage {
+    let kb = |blocks| blocks * stat.f_frsize / 1024;
+    FsUsage {
+        mountpoint: mount.mountpoint.clone(),
+        device: mount.source.clone(),
+        vbd: vbd_detect::get_vbd_id(&mount.source),
+        fstype: mount.fstype.to_string(),
+        size_kb: kb(stat.f_blocks),
+        used_kb: kb(stat.f_blocks - stat.f_bfree),
+        available_kb: kb(stat.f_bavail),
+        inodes_total: stat.f_files,
+        inodes_free: stat.f_ffree,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mountinfo_line() {
+        let line = "36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue";
+        assert_eq!(parse_mountinfo_line(line),
+                   Some(Mount { device_id: "98:0", mountpoint: "/mnt2".to_string(),
+                                options: "rw,noatime", fstype: "ext3",
+                                source: "/dev/root".to_string(),
+                                super_options: "rw,errors=continue" }));
+        // any number of optional fields before the separator
+        let line = "29 1 252:1 / / rw,relatime - xfs /dev/xvda1 rw,attr2";
+        assert_eq!(parse_mountinfo_line(line).unwrap().mountpoint, "/");
+        let line = "40 29 0:35 / /srv rw shared:5 master:2 propagate_from:1 unbindable \
+                    - zfs tank/srv rw,xattr";
+        let mount = parse_mountinfo_line(line).unwrap();
+        assert_eq!((mount.mountpoint.as_str(), mount.fstype, mount.source.as_str()),
+                   ("/srv", "zfs", "tank/srv"));
+        assert!(mount.is_local());
+        assert_eq!(parse_mountinfo_line("36 35 98:0 /mnt1 /mnt2 rw,noatime master:1"), None);
+        assert_eq!(parse_mountinfo_line("36 35 98:0 - ext3 /dev/root rw"), None);
+    }
+
+    #[test]
+    fn mountinfo_escapes() {
+        let line = "50 29 202:17 / /mnt/my\\040disk rw - ext4 /dev/xvdb1 rw";
+        assert_eq!(parse_mountinfo_line(line).unwrap().mountpoint, "/mnt/my disk");
+        assert_eq!(unescape("a\\011b\\012c\\134d"), "a\tb\nc\\d");
+        // not valid octal escapes, kept as is
+        assert_eq!(unescape("a\\b"), "a\\b");
+        assert_eq!(unescape("a\\09"), "a\\09");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+
+    #[test]
+    fn local_and_bind_mounts() {
+        let mountinfo = "\
+            29 1 202:1 / / rw,relatime - ext4 /dev/xvda1 rw\n\
+            30 29 0:5 / /proc rw - proc proc rw\n\
+            34 29 0:50 / /mnt/share rw - cifs //server/share rw\n\
+            35 29 0:51 / /mnt/nfs rw - nfs4 server:/export rw\n\
+            36 29 0:52 / /mnt/sshfs rw - fuse.sshfs user@host:/ rw\n\
+            31 29 202:17 / /data rw - ext4 /dev/xvdb1 rw\n\
+            32 29 202:17 /www /var/www rw - ext4 /dev/xvdb1 rw\n\
+            33 29 202:1 /tmp /tmp rw - ext4 /dev/xvda1 rw\n";
+        let mountpoints: Vec<String> = local_filesystems(mountinfo).into_iter()
+            .map(|mount| mount.mountpoint)
+            .collect();
+        assert_eq!(mountpoints, ["/", "/data"]);
+    }
+}
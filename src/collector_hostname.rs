@@ -0,0 +1,19 @@
+use crate::datastructs::HostnameInfo;
+use futures::stream::Stream;
+use std::io;
+
+pub struct HostnameSource {}
+
+impl HostnameSource {
+    pub fn new() -> io::Result<HostnameSource> {
+        Ok(HostnameSource {})
+    }
+
+    pub async fn collect_current(&mut self) -> io::Result<HostnameInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for hostname"))
+    }
+
+    pub fn stream(&mut self) -> impl Stream<Item = io::Result<HostnameInfo>> + '_ {
+        futures::stream::pending::<io::Result<HostnameInfo>>()
+    }
+}
@@ -0,0 +1,80 @@
+use crate::datastructs::HostnameInfo;
+use async_stream::try_stream;
+use futures::stream::Stream;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io;
+use std::{mem, ptr};
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+
+// the kernel signals hostname changes (sethostname(2), UTS namespace
+// changes) with POLLPRI on this file
+const PROC_HOSTNAME: &str = "/proc/sys/kernel/hostname";
+
+pub struct HostnameSource {
+    // last info we returned, to only report actual changes
+    current: Option<HostnameInfo>,
+}
+
+impl HostnameSource {
+    pub fn new() -> io::Result<HostnameSource> {
+        Ok(HostnameSource { current: None })
+    }
+
+    pub async fn collect_current(&mut self) -> io::Result<HostnameInfo> {
+        let hostname_info = get_hostname_info().await?;
+        self.current = Some(hostname_info.clone());
+        Ok(hostname_info)
+    }
+
+    pub fn stream(&mut self) -> impl Stream<Item = io::Result<HostnameInfo>> + '_ {
+        try_stream! {
+            let proc_hostname = AsyncFd::with_interest(File::open(PROC_HOSTNAME)?,
+                                                       Interest::PRIORITY)?;
+            loop {
+                let mut guard = proc_hostname.ready(Interest::PRIORITY).await?;
+                guard.clear_ready();
+                let hostname_info = get_hostname_info().await?;
+                if self.current.as_ref() != Some(&hostname_info) {
+                    log::debug!("hostname changed: {hostname_info:?}");
+                    self.current = Some(hostname_info.clone());
+                    yield hostname_info;
+                }
+            }
+        }
+    }
+}
+
+async fn get_hostname_info() -> io::Result<HostnameInfo> {
+    let hostname = uname::uname()?.nodename;
+    // name resolution may block for a long time
+    let fqdn = {
+        let hostname = hostname.clone();
+        tokio::task::spawn_blocking(move || resolve_fqdn(&hostname)).await?
+    };
+    Ok(HostnameInfo { hostname, fqdn })
+}
+
+// same as `hostname --fqdn`: canonical name as resolved by getaddrinfo(3)
+fn resolve_fqdn(hostname: &str) -> Option<String> {
+    let c_hostname = CString::new(hostname).ok()?;
+    let mut hints: libc::addrinfo = unsafe { mem::zeroed() };
+    hints.ai_family = libc::AF_UNSPEC;
+    hints.ai_flags = libc::AI_CANONNAME;
+    let mut result: *mut libc::addrinfo = ptr::null_mut();
+    let ret = unsafe { libc::getaddrinfo(c_hostname.as_ptr(), ptr::null(), &hints, &mut result) };
+    if ret != 0 {
+        let error = unsafe { CStr::from_ptr(libc::gai_strerror(ret)) };
+        log::debug!("cannot resolve {hostname}: {}", error.to_string_lossy());
+        return None;
+    }
+    let canonname = unsafe { (*result).ai_canonname };
+    let fqdn = if canonname.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(canonname) }.to_string_lossy().into_owned())
+    };
+    unsafe { libc::freeaddrinfo(result) };
+    fqdn
+}
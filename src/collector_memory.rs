@@ -1,3 +1,4 @@
+use crate::datastructs::MemInfo;
 use std::io;
 
 pub struct MemorySource {}
@@ -10,7 +11,7 @@ impl MemorySource {
     pub fn get_total_kb(&mut self) -> io::Result<usize> {
         Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for mem_total"))
     }
-    pub fn get_available_kb(&mut self) -> io::Result<usize> {
-        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for mem_avail"))
+    pub fn collect_meminfo(&mut self) -> io::Result<MemInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for meminfo"))
     }
 }
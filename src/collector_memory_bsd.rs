@@ -1,12 +1,37 @@
+use crate::datastructs::{MemInfo, SwapDevice};
+use crate::swap_rate::SwapRate;
+use std::convert::TryFrom;
+use std::ffi::CStr;
 use std::io;
+use std::mem;
+use std::ptr;
 use sysctl::Sysctl;
 
+// from vm/vm_param.h, what vm.swap_info.<index> returns
+const XSWDEV_VERSION: libc::c_uint = 2;
+#[repr(C)]
+struct Xswdev {
+    xsw_version: libc::c_uint,
+    xsw_dev: libc::dev_t,
+    xsw_flags: libc::c_int,
+    xsw_nblks: libc::c_int, // in pages
+    xsw_used: libc::c_int,
+}
+
 pub struct MemorySource {
     memtotal_ctl: sysctl::Ctl,
     pagesize: usize,
     meminactive_ctl: sysctl::Ctl,
     memcache_ctl: sysctl::Ctl,
     memfree_ctl: sysctl::Ctl,
+    // those are in bytes, and may be missing on older releases
+    bufspace_ctl: Option<sysctl::Ctl>,
+    uma_ctl: Option<sysctl::Ctl>,
+    swapreserved_ctl: Option<sysctl::Ctl>,
+    swaptotal_ctl: Option<sysctl::Ctl>,
+    swappgsin_ctl: Option<sysctl::Ctl>,
+    swappgsout_ctl: Option<sysctl::Ctl>,
+    swap_rate: SwapRate,
 }
 
 impl MemorySource {
@@ -22,22 +47,121 @@ impl MemorySource {
                           meminactive_ctl: new_sysctl("vm.stats.vm.v_inactive_count")?,
                           memcache_ctl: new_sysctl("vm.stats.vm.v_cache_count")?,
                           memfree_ctl: new_sysctl("vm.stats.vm.v_free_count")?,
+                          bufspace_ctl: new_optional_sysctl("vfs.bufspace"),
+                          uma_ctl: new_optional_sysctl("vm.uma_kmem_total"),
+                          swapreserved_ctl: new_optional_sysctl("vm.swap_reserved"),
+                          swaptotal_ctl: new_optional_sysctl("vm.swap_total"),
+                          swappgsin_ctl: new_optional_sysctl("vm.stats.vm.v_swappgsin"),
+                          swappgsout_ctl: new_optional_sysctl("vm.stats.vm.v_swappgsout"),
+                          swap_rate: SwapRate::new(pagesize),
         })
     }
 
     pub fn get_total_kb(&mut self) -> io::Result<usize> {
         Ok(get_field_ulong(&self.memtotal_ctl)? as usize / 1024)
     }
-    pub fn get_available_kb(&mut self) -> io::Result<usize> {
-        let available = (get_field_u32(&self.meminactive_ctl)? as usize +
-                         get_field_uint(&self.memcache_ctl)? as usize +
-                         get_field_u32(&self.memfree_ctl)? as usize) * self.pagesize;
-        Ok(available / 1024)
+
+    // mapped to the closest Linux /proc/meminfo equivalents
+    pub fn collect_meminfo(&mut self) -> io::Result<MemInfo> {
+        let inactive_kb = get_field_u32(&self.meminactive_ctl)? as usize * self.pagesize / 1024;
+        let cache_kb = get_field_uint(&self.memcache_ctl)? as usize * self.pagesize / 1024;
+        let free_kb = get_field_u32(&self.memfree_ctl)? as usize * self.pagesize / 1024;
+        let swap_rates = match (&self.swappgsin_ctl, &self.swappgsout_ctl) {
+            (Some(swappgsin_ctl), Some(swappgsout_ctl)) =>
+                match (get_field_number(swappgsin_ctl), get_field_number(swappgsout_ctl)) {
+                    (Ok(pages_in), Ok(pages_out)) => self.swap_rate.update(pages_in, pages_out),
+                    (Err(e), _) | (_, Err(e)) => {
+                        log::debug!("{e}");
+                        None
+                    },
+                },
+            _ => None,
+        };
+        let swap_devices = match self.collect_swap_devices() {
+            Ok(swap_devices) => Some(swap_devices),
+            Err(e) => {
+                log::warn!("cannot read swap devices: {e}");
+                None
+            },
+        };
+        Ok(MemInfo {
+            total_kb: self.get_total_kb()?,
+            available_kb: inactive_kb + cache_kb + free_kb,
+            free_kb: Some(free_kb),
+            buffers_kb: get_optional_field_kb(&self.bufspace_ctl),
+            // the page cache mostly lives in the inactive queue
+            cached_kb: Some(inactive_kb + cache_kb),
+            slab_kb: get_optional_field_kb(&self.uma_ctl),
+            committed_kb: get_optional_field_kb(&self.swapreserved_ctl),
+            swap_total_kb: get_optional_field_kb(&self.swaptotal_ctl),
+            // no sysctl for it
+            swap_free_kb: swap_devices.as_ref().map(|swap_devices| {
+                swap_devices.iter()
+                    .map(|device| device.size_kb.saturating_sub(device.used_kb))
+                    .sum()
+            }),
+            swap_in_kb_per_s: swap_rates.map(|(rate_in, _)| rate_in),
+            swap_out_kb_per_s: swap_rates.map(|(_, rate_out)| rate_out),
+            swap_devices: swap_devices.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+
+    // what swapinfo(8) shows, from vm.swap_info.<index> until ENOENT
+    fn collect_swap_devices(&self) -> io::Result<Vec<SwapDevice>> {
+        let mut mib = [0 as libc::c_int; 3];
+        let mut mib_len = mib.len() - 1;
+        let ctl_name = b"vm.swap_info\0".as_ptr() as *const libc::c_char;
+        if unsafe { libc::sysctlnametomib(ctl_name, mib.as_mut_ptr(), &mut mib_len) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut swap_devices = vec![];
+        for index in 0.. {
+            mib[mib_len] = index;
+            let mut xswdev: Xswdev = unsafe { mem::zeroed() };
+            let mut len = mem::size_of::<Xswdev>();
+            let ret = unsafe { libc::sysctl(mib.as_ptr(), (mib_len + 1) as libc::c_uint,
+                                            &mut xswdev as *mut Xswdev as *mut libc::c_void,
+                                            &mut len, ptr::null(), 0) };
+            if ret != 0 {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() == Some(libc::ENOENT) {
+                    break;
+                }
+                return Err(e);
+            }
+            if xswdev.xsw_version != XSWDEV_VERSION {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("unknown xswdev version {}",
+                                                  xswdev.xsw_version)));
+            }
+            let name = device_name(xswdev.xsw_dev);
+            // swap files go through md(4)
+            let kind = if name.starts_with("/dev/md") { "file" } else { "partition" };
+            swap_devices.push(SwapDevice {
+                kind: kind.to_string(),
+                name,
+                size_kb: xswdev.xsw_nblks as usize * self.pagesize / 1024,
+                used_kb: xswdev.xsw_used as usize * self.pagesize / 1024,
+                priority: None,
+            });
+        }
+        Ok(swap_devices)
+    }
+}
+
+fn device_name(dev: libc::dev_t) -> String {
+    let mut buf = [0 as libc::c_char; 64];
+    let name = unsafe { libc::devname_r(dev, libc::S_IFCHR, buf.as_mut_ptr(),
+                                        buf.len() as libc::c_int) };
+    if name.is_null() {
+        return format!("#{dev}");
     }
+    format!("/dev/{}", unsafe { CStr::from_ptr(name) }.to_string_lossy())
 }
 
 // helper to create a sysctl with errors mapped to io::Error
-fn new_sysctl(name: &str) -> io::Result<sysctl::Ctl> {
+pub fn new_sysctl(name: &str) -> io::Result<sysctl::Ctl> {
     match sysctl::Ctl::new(name) {
         Err(sysctl::SysctlError::NotFound(_)) =>
             Err(io::Error::new(io::ErrorKind::NotFound, format!("sysctl {} not found", name))),
@@ -48,6 +172,16 @@ fn new_sysctl(name: &str) -> io::Result<sysctl::Ctl> {
     }
 }
 
+fn new_optional_sysctl(name: &str) -> Option<sysctl::Ctl> {
+    match new_sysctl(name) {
+        Ok(ctl) => Some(ctl),
+        Err(e) => {
+            log::debug!("{e}");
+            None
+        },
+    }
+}
+
 fn sysctrlerror_to_ioerror(error: sysctl::SysctlError) -> io::Error {
     match error {
         e => io::Error::new(io::ErrorKind::Other, format!("sysctl error: {:?}", e)),
@@ -86,3 +220,29 @@ fn get_field_uint(ctl: &sysctl::Ctl) -> io::Result<u32> {
                                    ctl.name().map_err(sysctrlerror_to_ioerror)?, v)))
     }
 }
+
+// for sysctls whose integer type varies across releases
+pub fn get_field_number(ctl: &sysctl::Ctl) -> io::Result<u64> {
+    let v = ctl.value().map_err(sysctrlerror_to_ioerror)?;
+    let value = match &v {
+        sysctl::CtlValue::Int(value) | sysctl::CtlValue::S32(value) => u64::try_from(*value).ok(),
+        sysctl::CtlValue::Long(value) | sysctl::CtlValue::S64(value) => u64::try_from(*value).ok(),
+        sysctl::CtlValue::Uint(value) | sysctl::CtlValue::U32(value) => Some(*value as u64),
+        sysctl::CtlValue::Ulong(value) | sysctl::CtlValue::U64(value) => Some(*value),
+        _ => None,
+    };
+    value.ok_or(io::Error::new(io::ErrorKind::Other,
+                               format!("cannot interpret {} as a number: {:?}",
+                                       ctl.name().map_err(sysctrlerror_to_ioerror)?, v)))
+}
+
+// value in bytes of an optional sysctl, in KiB
+fn get_optional_field_kb(ctl: &Option<sysctl::Ctl>) -> Option<usize> {
+    match get_field_number(ctl.as_ref()?) {
+        Ok(value) => Some(value as usize / 1024),
+        Err(e) => {
+            log::debug!("{e}");
+            None
+        },
+    }
+}
@@ -1,39 +1,165 @@
-use std::fs::File;
+use crate::datastructs::{MemInfo, SwapDevice};
+use crate::swap_rate::SwapRate;
+use std::collections::HashMap;
+use std::fs::{self, File};
 use std::io::{self, Read, Seek};
 
+const PROC_SWAPS: &str = "/proc/swaps";
+const PROC_VMSTAT: &str = "/proc/vmstat";
+
 pub struct MemorySource {
     meminfo: File,
+    swap_rate: SwapRate,
 }
 
 impl MemorySource {
     pub fn new() -> io::Result<MemorySource> {
         let meminfo = File::open("/proc/meminfo")?;
-        Ok(MemorySource { meminfo })
+        let pagesize = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if pagesize <= 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(MemorySource { meminfo, swap_rate: SwapRate::new(pagesize as usize) })
     }
 
     pub fn get_total_kb(&mut self) -> io::Result<usize> {
-        self.get_num_field("MemTotal:")
-    }
-    pub fn get_available_kb(&mut self) -> io::Result<usize> {
-        self.get_num_field("MemAvailable:")
+        Ok(self.collect_meminfo()?.total_kb)
     }
 
-    fn get_num_field(&mut self, tag: &str) -> io::Result<usize> {
+    pub fn collect_meminfo(&mut self) -> io::Result<MemInfo> {
         self.meminfo.rewind()?;
         let mut rawdata = String::new();
         self.meminfo.read_to_string(&mut rawdata)?;
-        let tagindex = rawdata
-            .find(tag)
-            .ok_or(io::Error::new(io::ErrorKind::InvalidData,
-                                  format!("could not find {tag}")))?;
-        let numindex = rawdata[tagindex + tag.len()..]
-            .find(|c: char| c.is_ascii_digit())
+        let fields = parse_meminfo(&rawdata);
+        let field = |tag: &str| fields.get(tag).copied();
+        let required_field = |tag: &str| {
+            field(tag).ok_or(io::Error::new(io::ErrorKind::InvalidData,
+                                            format!("could not find {tag}")))
+        };
+        let swap_rates = match read_swapped_pages() {
+            Ok((pages_in, pages_out)) => self.swap_rate.update(pages_in, pages_out),
+            Err(e) => {
+                log::debug!("{e}");
+                None
+            },
+        };
+        Ok(MemInfo {
+            total_kb: required_field("MemTotal")?,
+            available_kb: required_field("MemAvailable")?,
+            free_kb: field("MemFree"),
+            buffers_kb: field("Buffers"),
+            cached_kb: field("Cached"),
+            shmem_kb: field("Shmem"),
+            slab_kb: field("Slab"),
+            committed_kb: field("Committed_AS"),
+            swap_total_kb: field("SwapTotal"),
+            swap_free_kb: field("SwapFree"),
+            swap_in_kb_per_s: swap_rates.map(|(rate_in, _)| rate_in),
+            swap_out_kb_per_s: swap_rates.map(|(_, rate_out)| rate_out),
+            swap_devices: collect_swap_devices().unwrap_or_else(|e| {
+                log::warn!("cannot read swap devices: {e}");
+                vec![]
+            }),
+            hugepages_total: field("HugePages_Total"),
+            hugepages_free: field("HugePages_Free"),
+            hugepages_reserved: field("HugePages_Rsvd"),
+            hugepages_surplus: field("HugePages_Surp"),
+            hugepage_size_kb: field("Hugepagesize"),
+        })
+    }
+}
+
+// "Tag:   1234 kB" lines, the unit is omitted for page counts
+fn parse_meminfo(rawdata: &str) -> HashMap<&str, usize> {
+    rawdata.lines()
+        .filter_map(|line| {
+            let (tag, value) = line.split_once(':')?;
+            let value = value.split_whitespace().next()?.parse().ok()?;
+            Some((tag, value))
+        })
+        .collect()
+}
+
+// (pages in, pages out) since boot
+fn read_swapped_pages() -> io::Result<(u64, u64)> {
+    let vmstat = fs::read_to_string(PROC_VMSTAT)?;
+    let counter = |tag: &str| {
+        vmstat.lines()
+            .find_map(|line| line.strip_prefix(tag)?.strip_prefix(' ')?.parse().ok())
             .ok_or(io::Error::new(io::ErrorKind::InvalidData,
-                                  format!("no number after {tag}")))?;
-        let num_start = tagindex + tag.len() + numindex;
-        let num_end = num_start + (rawdata[num_start..]
-                                   .find(|c: char| !c.is_ascii_digit())
-                                   .unwrap());
-        Ok(rawdata[num_start..num_end].parse().unwrap())
+                                  format!("could not find {tag} in {PROC_VMSTAT}")))
+    };
+    Ok((counter("pswpin")?, counter("pswpout")?))
+}
+
+fn collect_swap_devices() -> io::Result<Vec<SwapDevice>> {
+    match fs::read_to_string(PROC_SWAPS) {
+        Ok(swaps) => Ok(parse_swaps(&swaps)),
+        // no swap support in the kernel
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(io::Error::new(e.kind(), format!("{PROC_SWAPS}: {e}"))),
+    }
+}
+
+// "Filename Type Size Used Priority" header, then one line per
+// device, sizes in KiB
+fn parse_swaps(swaps: &str) -> Vec<SwapDevice> {
+    swaps.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            // spaces in names are escaped as "\040"
+            let name = columns.next()?.replace("\\040", " ");
+            let kind = columns.next()?;
+            let size_kb = columns.next()?.parse().ok()?;
+            let used_kb = columns.next()?.parse().ok()?;
+            let priority = columns.next()?.parse().ok();
+            // compressed RAM looks like a partition
+            let kind = if name.starts_with("/dev/zram") { "zram" } else { kind };
+            Some(SwapDevice { kind: kind.to_string(), name, size_kb, used_kb, priority })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meminfo() {
+        let fields = parse_meminfo("MemTotal:        8048276 kB\n\
+                                    MemAvailable:    5612348 kB\n\
+                                    SwapFree:              0 kB\n\
+                                    HugePages_Total:       4\n\
+                                    Hugepagesize:       2048 kB\n\
+                                    DirectMap4k:      x kB\n\
+                                    no colon 12 kB\n\
+                                    Empty:\n");
+        assert_eq!(fields, HashMap::from([("MemTotal", 8048276), ("MemAvailable", 5612348),
+                                          ("SwapFree", 0), ("HugePages_Total", 4),
+                                          ("Hugepagesize", 2048)]));
+    }
+
+    #[test]
+    fn swaps() {
+        let swaps = parse_swaps("Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n\
+                                 /dev/xvda3                              partition\t2097148\t\t1024\t\t-2\n\
+                                 /var/swap\\040file                       file\t\t524284\t\t0\t\t-3\n\
+                                 /dev/zram0                              partition\t4020220\t\t0\t\t100\n");
+        assert_eq!(swaps, [
+            SwapDevice { kind: "partition".to_string(), name: "/dev/xvda3".to_string(),
+                         size_kb: 2097148, used_kb: 1024, priority: Some(-2) },
+            SwapDevice { kind: "file".to_string(), name: "/var/swap file".to_string(),
+                         size_kb: 524284, used_kb: 0, priority: Some(-3) },
+            SwapDevice { kind: "zram".to_string(), name: "/dev/zram0".to_string(),
+                         size_kb: 4020220, used_kb: 0, priority: Some(100) },
+        ]);
+        // no swap
+        assert_eq!(parse_swaps("Filename Type Size Used Priority\n"), []);
+        assert_eq!(parse_swaps(""), []);
+        // truncated or garbled lines
+        assert_eq!(parse_swaps("Filename Type Size Used Priority\n\
+                                /dev/xvda3 partition 2097148\n\
+                                /dev/xvda4 partition x 0 -2\n"), []);
     }
 }
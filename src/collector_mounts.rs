@@ -0,0 +1,20 @@
+use crate::datastructs::MountEvent;
+use futures::stream::Stream;
+use std::io;
+use std::time::Duration;
+
+pub struct MountSource {}
+
+impl MountSource {
+    pub fn new(_recheck_period: Duration, _jitter_percent: u8) -> io::Result<MountSource> {
+        Ok(MountSource {})
+    }
+
+    pub fn collect_current(&mut self) -> io::Result<Vec<MountEvent>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for mount events"))
+    }
+
+    pub fn stream(&mut self) -> impl Stream<Item = Vec<MountEvent>> + '_ {
+        futures::stream::pending::<Vec<MountEvent>>()
+    }
+}
@@ -0,0 +1,134 @@
+use crate::collector_fs::{parse_mountinfo_line, read_mountinfo, PROC_MOUNTINFO};
+use crate::datastructs::{MountEntry, MountEvent, MountEventOp};
+use crate::periodic::JitteredInterval;
+use async_stream::stream;
+use futures::stream::Stream;
+use std::fs::File;
+use std::io;
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+
+// The kernel signals changes to the mount table with POLLPRI on
+// mountinfo.  A filesystem turning read-only after I/O errors only
+// changes its superblock flags though, so we also recheck
+// periodically.
+
+pub struct MountSource {
+    recheck_period: Duration,
+    jitter_percent: u8,
+    // local mounts, in mountinfo order
+    current: Vec<MountEntry>,
+}
+
+impl MountSource {
+    pub fn new(recheck_period: Duration, jitter_percent: u8) -> io::Result<MountSource> {
+        Ok(MountSource { recheck_period, jitter_percent, current: vec![] })
+    }
+
+    // all current mounts, as Mounted events
+    pub fn collect_current(&mut self) -> io::Result<Vec<MountEvent>> {
+        self.current = read_mounts()?;
+        Ok(self.current.iter()
+           .map(|mount| MountEvent { op: MountEventOp::Mounted, mount: mount.clone() })
+           .collect())
+    }
+
+    // errors are logged and retried on the next recheck, in the
+    // meantime we only recheck periodically
+    pub fn stream(&mut self) -> impl Stream<Item = Vec<MountEvent>> + '_ {
+        stream! {
+            let mut proc_mountinfo = open_mountinfo();
+            // we just collected them
+            let mut recheck_timer = JitteredInterval::new(self.recheck_period,
+                                                          self.jitter_percent);
+            recheck_timer.reset();
+            loop {
+                let ready = match &proc_mountinfo {
+                    Some(fd) => tokio::select! {
+                        guard = fd.ready(Interest::PRIORITY) => Some(guard),
+                        _ = recheck_timer.tick() => None,
+                    },
+                    None => {
+                        recheck_timer.tick().await;
+                        proc_mountinfo = open_mountinfo();
+                        None
+                    },
+                };
+                match ready {
+                    Some(Ok(mut guard)) => guard.clear_ready(),
+                    Some(Err(e)) => {
+                        log::warn!("cannot watch {PROC_MOUNTINFO}, rechecking periodically: {e}");
+                        proc_mountinfo = None;
+                    },
+                    None => (),
+                }
+                match self.update() {
+                    Ok(events) if !events.is_empty() => yield events,
+                    Ok(_) => (),
+                    Err(e) => log::warn!("cannot read mounts, retrying later: {e}"),
+                }
+            }
+        }
+    }
+
+    fn update(&mut self) -> io::Result<Vec<MountEvent>> {
+        let mounts = read_mounts()?;
+        let mut events = vec![];
+        for old_mount in &self.current {
+            if !mounts.iter().any(|mount| mount.mountpoint == old_mount.mountpoint) {
+                log::info!("{} unmounted", old_mount.mountpoint);
+                events.push(MountEvent { op: MountEventOp::Unmounted, mount: old_mount.clone() });
+            }
+        }
+        for mount in &mounts {
+            match self.current.iter().find(|old_mount| old_mount.mountpoint == mount.mountpoint) {
+                None => {
+                    log::info!("{} mounted from {}", mount.mountpoint, mount.device);
+                    events.push(MountEvent { op: MountEventOp::Mounted, mount: mount.clone() });
+                },
+                Some(old_mount) if old_mount != mount => {
+                    if mount.read_only && !old_mount.read_only {
+                        log::warn!("{} is now read-only", mount.mountpoint);
+                    }
+                    events.push(MountEvent { op: MountEventOp::Changed, mount: mount.clone() });
+                },
+                Some(_) => (),
+            }
+        }
+        self.current = mounts;
+        Ok(events)
+    }
+}
+
+fn open_mountinfo() -> Option<AsyncFd<File>> {
+    match File::open(PROC_MOUNTINFO)
+        .and_then(|file| AsyncFd::with_interest(file, Interest::PRIORITY)) {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            log::warn!("cannot watch {PROC_MOUNTINFO}, rechecking periodically: {e}");
+            None
+        },
+    }
+}
+
+fn read_mounts() -> io::Result<Vec<MountEntry>> {
+    let mountinfo = read_mountinfo()?;
+    let mut mounts: Vec<MountEntry> = vec![];
+    for mount in mountinfo.lines().filter_map(parse_mountinfo_line) {
+        if !mount.is_local() {
+            continue;
+        }
+        let has_ro = |options: &str| options.split(',').any(|option| option == "ro");
+        // when mounted over, only the last one is visible
+        mounts.retain(|other| other.mountpoint != mount.mountpoint);
+        mounts.push(MountEntry {
+            read_only: has_ro(mount.options) || has_ro(mount.super_options),
+            options: mount.options.to_string(),
+            fstype: mount.fstype.to_string(),
+            device: mount.source,
+            mountpoint: mount.mountpoint,
+        });
+    }
+    Ok(mounts)
+}
@@ -1,4 +1,7 @@
-use crate::datastructs::{NetEvent, NetEventOp, NetInterface, NetInterfaceCache};
+use crate::datastructs::{NetCollectorConfig, NetEvent, NetEventOp, NetInterface,
+                         NetInterfaceCache, NetInterfaceKey, NetInterfaceKind, NetLinkState,
+                         NetNamespace, NetNamespaceSelection, NetOperState, NetRoute, NetStats};
+use crate::periodic::JitteredInterval;
 use async_stream::try_stream;
 use futures::channel::mpsc::UnboundedReceiver;
 use futures::stream::{Stream, StreamExt};
@@ -6,107 +9,300 @@ use netlink_packet_core::{
     NetlinkHeader, NetlinkMessage, NetlinkPayload, NLM_F_DUMP, NLM_F_REQUEST,
 };
 use netlink_packet_route::{
-    address, address::AddressMessage, link, link::LinkMessage, RouteNetlinkMessage,
+    address, address::AddressMessage, link, link::LinkMessage,
+    route, route::RouteMessage, AddressFamily, RouteNetlinkMessage,
 };
 use netlink_proto::{
     self, new_connection,
     sys::{protocols::NETLINK_ROUTE, AsyncSocket, SocketAddr},
 };
-use rtnetlink::constants::{RTMGRP_IPV4_IFADDR, RTMGRP_IPV6_IFADDR, RTMGRP_LINK};
+use rtnetlink::constants::{RTMGRP_IPV4_IFADDR, RTMGRP_IPV4_ROUTE,
+                           RTMGRP_IPV6_IFADDR, RTMGRP_IPV6_ROUTE, RTMGRP_LINK};
 use std::cell::RefCell;
-use std::collections::hash_map;
+use std::collections::{hash_map, HashMap, HashSet};
 use std::error::Error;
+use std::fs;
 use std::io;
-use std::net::IpAddr;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::fd::AsRawFd;
+use std::path::Path;
 use std::rc::Rc;
+use std::task::Poll;
 use std::vec::Vec;
 
+type NlHandle = netlink_proto::ConnectionHandle<RouteNetlinkMessage>;
+type NlMessages = UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>;
+
+// one per watched network namespace
+struct NlConnection {
+    netns: NetNamespace,
+    handle: NlHandle,
+    messages: NlMessages,
+}
+
+// addresses notified so far, by iface
+type NlAddresses = HashMap<NetInterfaceKey, HashSet<IpAddr>>;
+// routes notified so far, by (iface, destination, prefix length)
+type NlRoutes = HashMap<(NetInterfaceKey, IpAddr, u8), NetRoute>;
+
 pub struct NetworkSource {
-    handle: netlink_proto::ConnectionHandle<RouteNetlinkMessage>,
-    messages: UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>,
+    config: NetCollectorConfig,
+    connections: Vec<NlConnection>,
     iface_cache: &'static mut NetInterfaceCache,
+    addresses: NlAddresses,
+    routes: NlRoutes,
 }
 
+// Maximum number of non-default routes to report, we don't want to
+// flood xenstore with the routing table of a router guest.  Default
+// routes are always reported.
+const MAX_OTHER_ROUTES: usize = 16;
+
+// where iproute2 keeps its named network namespaces
+const NETNS_RUN_DIR: &str = "/run/netns";
+
 impl NetworkSource {
-    pub fn new(iface_cache: &'static mut NetInterfaceCache) -> io::Result<NetworkSource> {
-        let (mut connection, handle, messages) = new_connection(NETLINK_ROUTE)?;
-        // What kinds of broadcast messages we want to listen for.
-        let nl_mgroup_flags = RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR;
-        let nl_addr = SocketAddr::new(0, nl_mgroup_flags);
-        connection
-            .socket_mut()
-            .socket_mut()
-            .bind(&nl_addr)
-            .expect("failed to bind");
-        tokio::spawn(connection);
-        Ok(NetworkSource { handle, messages, iface_cache })
+    pub fn new(iface_cache: &'static mut NetInterfaceCache,
+               config: &NetCollectorConfig) -> io::Result<NetworkSource> {
+        let connections = nl_connect_all(&config.netns)?;
+        Ok(NetworkSource { config: config.clone(), connections, iface_cache,
+                           addresses: HashMap::new(), routes: HashMap::new() })
+    }
+
+    // Replace connections after one of them has ended, and get the
+    // events we may have missed meanwhile
+    pub async fn reconnect(&mut self) -> Result<Vec<NetEvent>, Box<dyn Error>> {
+        self.connections = nl_connect_all(&self.config.netns)?;
+        Ok(self.resync().await?)
     }
 
     pub async fn collect_current(&mut self) -> Result<Vec<NetEvent>, Box<dyn Error>> {
         let mut events = Vec::<NetEvent>::new();
 
-        // Create the netlink message that requests the links to be dumped
-        let mut nl_hdr = NetlinkHeader::default();
-        nl_hdr.flags = NLM_F_DUMP | NLM_F_REQUEST;
-        let nl_msg = NetlinkMessage::new(
-            nl_hdr,
-            RouteNetlinkMessage::GetLink(LinkMessage::default()).into(),
-        );
-        // Send the request
-        let mut nl_response = self.handle.request(nl_msg, SocketAddr::new(0, 0))?;
-        // Handle response
-        while let Some(packet) = nl_response.next().await {
-            if let NetlinkMessage{payload: NetlinkPayload::InnerMessage(msg), ..} = packet {
-                events.extend(self.netevent_from_rtnetlink(&msg)?);
+        for conn in 0..self.connections.len() {
+            events.extend(self.collect_conn(conn).await?);
+        }
+
+        Ok(events)
+    }
+
+    async fn collect_conn(&mut self, conn: usize) -> io::Result<Vec<NetEvent>> {
+        let mut events = Vec::<NetEvent>::new();
+        // links first, so addresses and routes can be attached to them
+        events.extend(self.nl_dump(conn, RouteNetlinkMessage::GetLink(
+            LinkMessage::default())).await?);
+        events.extend(self.nl_dump(conn, RouteNetlinkMessage::GetAddress(
+            AddressMessage::default())).await?);
+        events.extend(self.nl_dump(conn, RouteNetlinkMessage::GetRoute(
+            RouteMessage::default())).await?);
+        Ok(events)
+    }
+
+    // Follow namespaces created or deleted since we connected
+    async fn rescan_netns(&mut self) -> io::Result<Vec<NetEvent>> {
+        let names = netns_names(&self.config.netns);
+        let mut events = Vec::<NetEvent>::new();
+        // our socket would keep a deleted namespace alive
+        let (gone, kept): (Vec<_>, Vec<_>) = mem::take(&mut self.connections).into_iter()
+            .partition(|conn| conn.netns.as_ref().is_some_and(|name| !names.contains(name)));
+        self.connections = kept;
+        for conn in gone {
+            log::info!("network namespace {} is gone", conn.netns.as_deref().unwrap_or(""));
+            let keys: Vec<_> = self.iface_cache.keys()
+                .filter(|(netns, _)| *netns == conn.netns)
+                .cloned()
+                .collect();
+            for key in keys {
+                self.addresses.remove(&key);
+                self.routes.retain(|(route_iface, _, _), _| *route_iface != key);
+                if let Some(iface) = self.iface_cache.remove(&key) {
+                    events.push(NetEvent{iface, op: NetEventOp::RmIface});
+                }
             }
         }
+        for name in names {
+            if self.connections.iter().any(|conn| conn.netns.as_ref() == Some(&name)) {
+                continue;
+            }
+            match nl_connect_netns(&name)? {
+                Ok((handle, messages)) => {
+                    log::info!("watching network namespace {name}");
+                    self.connections.push(NlConnection { netns: Some(name), handle, messages });
+                    events.extend(self.collect_conn(self.connections.len() - 1).await?);
+                },
+                // e.g. not set up completely yet, we will retry
+                Err(e) => log::debug!("cannot watch network namespace {name}: {e}"),
+            }
+        }
+        Ok(events)
+    }
 
-        // Create the netlink message that requests the addresses to be dumped
+    // request a dump of given object kind, and decode the response
+    async fn nl_dump(&mut self, conn: usize, request: RouteNetlinkMessage)
+                     -> io::Result<Vec<NetEvent>> {
+        let mut events = Vec::<NetEvent>::new();
+        for msg in self.nl_dump_messages(conn, request).await? {
+            events.extend(self.netevent_from_rtnetlink(conn, &msg)?);
+        }
+        Ok(events)
+    }
+
+    async fn nl_dump_messages(&mut self, conn: usize, request: RouteNetlinkMessage)
+                              -> io::Result<Vec<RouteNetlinkMessage>> {
+        let mut messages = Vec::<RouteNetlinkMessage>::new();
+
+        // Create the netlink message that requests the objects to be dumped
         let mut nl_hdr = NetlinkHeader::default();
         nl_hdr.flags = NLM_F_DUMP | NLM_F_REQUEST;
-        let nl_msg = NetlinkMessage::new(
-            nl_hdr,
-            RouteNetlinkMessage::GetAddress(AddressMessage::default()).into(),
-        );
+        let nl_msg = NetlinkMessage::new(nl_hdr, request.into());
         // Send the request
-        let mut nl_response = self.handle.request(nl_msg, SocketAddr::new(0, 0))?;
+        let mut nl_response = self.connections[conn].handle.request(nl_msg, SocketAddr::new(0, 0))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
         // Handle response
         while let Some(packet) = nl_response.next().await {
             if let NetlinkMessage{payload: NetlinkPayload::InnerMessage(msg), ..} = packet {
-                events.extend(self.netevent_from_rtnetlink(&msg)?);
+                messages.push(msg);
             }
         }
 
-        Ok(events)
+        Ok(messages)
     }
 
     pub fn stream(&mut self) -> impl Stream<Item = io::Result<NetEvent>> + '_ {
         try_stream! {
-            while let Some((message, _)) = self.messages.next().await {
-                if let NetlinkMessage{payload: NetlinkPayload::InnerMessage(msg), ..} = message {
-                    for event in self.netevent_from_rtnetlink(&msg)? {
-                        yield event;
-                    }
+            let mut stats_timer = JitteredInterval::new(self.config.stats_period,
+                                                        self.config.jitter_percent);
+            // namespaces come and go with the containers using them
+            let watch_netns = !matches!(self.config.netns, NetNamespaceSelection::None);
+            let mut netns_timer = JitteredInterval::new(self.config.poll_period,
+                                                        self.config.jitter_percent);
+            netns_timer.reset();
+            loop {
+                let events = tokio::select! {
+                    message = nl_next_message(&mut self.connections) => match message {
+                        Some((conn, NetlinkMessage{payload: NetlinkPayload::InnerMessage(msg), ..})) =>
+                            match self.netevent_from_rtnetlink(conn, &msg) {
+                                Ok(events) => Ok(events),
+                                // likely we missed some messages, eg. the
+                                // creation of the iface for an address
+                                Err(e) => {
+                                    log::warn!("cannot decode netlink message, resyncing: {e}");
+                                    self.resync().await
+                                },
+                            },
+                        Some((_, NetlinkMessage{payload: NetlinkPayload::Error(err), ..})) => {
+                            log::warn!("netlink error {err:?}, resyncing");
+                            self.resync().await
+                        },
+                        Some(_) => Ok(vec![]),
+                        // an overrun (ENOBUFS) closes the connection,
+                        // the reconnection resyncs
+                        None => break,
+                    },
+                    _ = stats_timer.tick() => self.collect_stats().await,
+                    _ = netns_timer.tick(), if watch_netns => self.rescan_netns().await,
+                };
+                for event in events? {
+                    yield event;
                 }
+            }
+        }
+    }
+
+    // Get a fresh full view, and emit the events needed to get the
+    // publisher in sync with it
+    async fn resync(&mut self) -> io::Result<Vec<NetEvent>> {
+        let known_ifaces: Vec<_> = self.iface_cache.values().cloned().collect();
+        let known_addresses = mem::take(&mut self.addresses);
+        let known_routes = mem::take(&mut self.routes);
+
+        // this refills iface_cache, addresses and routes
+        let current_events = self.collect_current().await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
+        let current_keys: HashSet<NetInterfaceKey> = current_events.iter()
+            .filter(|event| matches!(event.op, NetEventOp::AddIface))
+            .map(|event| event.iface.borrow().key())
+            .collect();
+        // removals first, as they may free resources for additions
+        let mut events = vanished_events(known_ifaces, &known_addresses, known_routes,
+                                         &current_keys, self.iface_cache,
+                                         &mut self.addresses, &self.routes);
+
+        // re-notifying things already known is harmless
+        events.extend(current_events);
+        Ok(events)
+    }
+
+    // interface counters, from a link dump
+    async fn collect_stats(&mut self) -> io::Result<Vec<NetEvent>> {
+        let mut events = Vec::<NetEvent>::new();
+        for conn in 0..self.connections.len() {
+            events.extend(self.collect_conn_stats(conn).await?);
+        }
+        Ok(events)
+    }
+
+    async fn collect_conn_stats(&mut self, conn: usize) -> io::Result<Vec<NetEvent>> {
+        let mut events = Vec::<NetEvent>::new();
+        let request = RouteNetlinkMessage::GetLink(LinkMessage::default());
+        for msg in self.nl_dump_messages(conn, request).await? {
+            let RouteNetlinkMessage::NewLink(LinkMessage{header, attributes, ..}) = msg else {
+                continue;
             };
+            let key = (self.connections[conn].netns.clone(), header.index);
+            let Some(iface) = self.iface_cache.get(&key) else {
+                continue;
+            };
+            for nla in attributes {
+                if let link::LinkAttribute::Stats64(stats) = nla {
+                    let stats = NetStats {
+                        rx_bytes: stats.rx_bytes,
+                        rx_packets: stats.rx_packets,
+                        rx_errors: stats.rx_errors,
+                        rx_dropped: stats.rx_dropped,
+                        tx_bytes: stats.tx_bytes,
+                        tx_packets: stats.tx_packets,
+                        tx_errors: stats.tx_errors,
+                        tx_dropped: stats.tx_dropped,
+                    };
+                    if iface.borrow_mut().update_stats(stats, &self.config.stats_threshold) {
+                        events.push(NetEvent{iface: iface.clone(), op: NetEventOp::Stats(stats)});
+                    }
+                }
+            }
         }
+        Ok(events)
     }
 
-    fn netevent_from_rtnetlink(&mut self, nl_msg: &RouteNetlinkMessage)
+    fn netevent_from_rtnetlink(&mut self, conn: usize, nl_msg: &RouteNetlinkMessage)
                                -> io::Result<Vec<NetEvent>> {
         let mut events = Vec::<NetEvent>::new();
         match nl_msg {
             RouteNetlinkMessage::NewLink(link_msg) => {
-                let (iface, mac_address) = self.nl_linkmessage_decode(link_msg)?;
-                log::debug!("NewLink({iface:?} {mac_address:?})");
+                let (iface, mac_address, link_state) = self.nl_linkmessage_decode(conn, link_msg)?;
+                log::debug!("NewLink({iface:?} {mac_address:?} {link_state:?})");
                 events.push(NetEvent{iface: iface.clone(), op: NetEventOp::AddIface});
                 if let Some(mac_address) = mac_address {
-                    events.push(NetEvent{iface, op: NetEventOp::AddMac(mac_address)});
+                    events.push(NetEvent{iface: iface.clone(), op: NetEventOp::AddMac(mac_address)});
+                }
+                // only notify link state when it changed
+                let link_changed = iface.borrow().link_state != Some(link_state);
+                if link_changed {
+                    iface.borrow_mut().link_state = Some(link_state);
+                    events.push(NetEvent{iface, op: NetEventOp::LinkState(link_state)});
                 }
             },
             RouteNetlinkMessage::DelLink(link_msg) => {
-                let (iface, mac_address) = self.nl_linkmessage_decode(link_msg)?;
+                let (iface, mac_address, _) = self.nl_linkmessage_decode(conn, link_msg)?;
                 log::debug!("DelLink({iface:?} {mac_address:?})");
+                // the kernel does not always notify removal of routes
+                // going away with their interface
+                let key = iface.borrow().key();
+                self.routes.retain(|(route_iface, _, _), _| *route_iface != key);
+                self.addresses.remove(&key);
+                self.iface_cache.remove(&key);
                 if let Some(mac_address) = mac_address {
                     events.push(NetEvent{iface: iface.clone(),
                                          op: NetEventOp::RmMac(mac_address)}); // redundant
@@ -114,34 +310,77 @@ impl NetworkSource {
             events.push(NetEvent{iface, op: NetEventOp::RmIface});
             },
             RouteNetlinkMessage::NewAddress(address_msg) => {
-                // FIXME does not distinguish when IP is on DOWN iface
-                let (iface, address) = self.nl_addressmessage_decode(address_msg)?;
+                // an IP on a DOWN iface is still reported, link state
+                // is notified separately
+                let (iface, address) = self.nl_addressmessage_decode(conn, address_msg)?;
                 log::debug!("NewAddress({iface:?} {address})");
+                self.addresses.entry(iface.borrow().key()).or_default().insert(address);
                 events.push(NetEvent{iface, op: NetEventOp::AddIp(address)});
             },
             RouteNetlinkMessage::DelAddress(address_msg) => {
-                let (iface, address) = self.nl_addressmessage_decode(address_msg)?;
+                let (iface, address) = self.nl_addressmessage_decode(conn, address_msg)?;
                 log::debug!("DelAddress({iface:?} {address})");
+                if let Some(addresses) = self.addresses.get_mut(&iface.borrow().key()) {
+                    addresses.remove(&address);
+                }
                 events.push(NetEvent{iface, op: NetEventOp::RmIp(address)});
             },
+            RouteNetlinkMessage::NewRoute(route_msg) => {
+                if let Some((iface, route)) = self.nl_routemessage_decode(conn, route_msg) {
+                    log::debug!("NewRoute({iface:?} {route:?})");
+                    let key = (iface.borrow().key(), route.destination, route.prefix_len);
+                    match self.routes.get(&key) {
+                        Some(known_route) if *known_route == route => (),
+                        Some(_) => {
+                            // replaced route, publisher will overwrite
+                            self.routes.insert(key, route.clone());
+                            events.push(NetEvent{iface, op: NetEventOp::AddRoute(route)});
+                        },
+                        None => {
+                            let num_other_routes = self.routes.values()
+                                .filter(|route| !route.is_default())
+                                .count();
+                            if route.is_default() || num_other_routes < MAX_OTHER_ROUTES {
+                                self.routes.insert(key, route.clone());
+                                events.push(NetEvent{iface, op: NetEventOp::AddRoute(route)});
+                            } else {
+                                log::debug!("not reporting route {route:?}, limit reached");
+                            }
+                        },
+                    }
+                }
+            },
+            RouteNetlinkMessage::DelRoute(route_msg) => {
+                if let Some((iface, route)) = self.nl_routemessage_decode(conn, route_msg) {
+                    log::debug!("DelRoute({iface:?} {route:?})");
+                    let key = (iface.borrow().key(), route.destination, route.prefix_len);
+                    if let Some(route) = self.routes.remove(&key) {
+                        events.push(NetEvent{iface, op: NetEventOp::RmRoute(route)});
+                    }
+                }
+            },
             _ => {
-                return Err(io::Error::new(io::ErrorKind::InvalidData,
-                                          format!("unhandled RouteNetlinkMessage: {nl_msg:?}")));
+                log::debug!("ignoring unhandled RouteNetlinkMessage: {nl_msg:?}");
             },
         };
         Ok(events)
     }
 
     fn nl_linkmessage_decode(
-        &mut self, msg: &LinkMessage
+        &mut self, conn: usize, msg: &LinkMessage
     ) -> io::Result<(Rc<RefCell<NetInterface>>, // ref to the (possibly new) impacted interface
                      Option<String>,           // MAC address
+                     NetLinkState,
     )> {
         let LinkMessage{header, attributes, ..} = msg;
 
         // extract fields of interest
         let mut iface_name: Option<String> = None;
         let mut address_bytes: Option<&Vec<u8>> = None;
+        let mut oper_state: Option<&link::State> = None;
+        let mut carrier: Option<bool> = None;
+        let mut info_kind: Option<&link::InfoKind> = None;
+        let mut perm_address_bytes: Option<&Vec<u8>> = None;
         for nla in attributes {
             if let link::LinkAttribute::IfName(name) = nla {
                 iface_name = Some(name.to_string());
@@ -149,16 +388,40 @@ impl NetworkSource {
             if let link::LinkAttribute::Address(addr) = nla {
                 address_bytes = Some(addr);
             }
+            if let link::LinkAttribute::OperState(state) = nla {
+                oper_state = Some(state);
+            }
+            if let link::LinkAttribute::Carrier(value) = nla {
+                carrier = Some(*value != 0);
+            }
+            if let link::LinkAttribute::PermAddress(addr) = nla {
+                perm_address_bytes = Some(addr);
+            }
+            if let link::LinkAttribute::LinkInfo(infos) = nla {
+                info_kind = infos.iter().find_map(|info| match info {
+                    link::LinkInfo::Kind(kind) => Some(kind),
+                    _ => None,
+                });
+            }
         }
+        let link_state = NetLinkState {
+            admin_up: header.flags.contains(&link::LinkFlag::Up),
+            // IFLA_CARRIER is not sent by older kernels, IFF_LOWER_UP
+            // carries the same information
+            carrier: carrier.unwrap_or(header.flags.contains(&link::LinkFlag::LowerUp)),
+            oper: oper_state.map_or(NetOperState::Unknown, nl_operstate_decode),
+        };
         // make sure message contains an address
-        let mac_address = address_bytes.map(|address_bytes| address_bytes.iter()
-                                            .map(|b| format!("{b:02x}"))
-                                            .collect::<Vec<String>>().join(":"));
+        let mac_address = address_bytes.map(|bytes| nl_macaddress_decode(bytes));
+        let perm_mac_address = perm_address_bytes.map(|bytes| nl_macaddress_decode(bytes));
 
         let iface = self.iface_cache
-            .entry(header.index)
-            .or_insert_with_key(|index|
-                                RefCell::new(NetInterface::new(*index, iface_name.clone()))
+            .entry((self.connections[conn].netns.clone(), header.index))
+            .or_insert_with_key(|(netns, index)|
+                                RefCell::new(NetInterface::new(
+                                    netns.clone(), *index, iface_name.clone(),
+                                    mac_address.as_deref(),
+                                    nl_kind_decode(info_kind, header.link_layer_type)))
                                 .into());
 
         // handle renaming
@@ -169,11 +432,19 @@ impl NetworkSource {
                 iface.borrow_mut().name = iface_name;
             }
         };
+        let altnames = nl_altnames_decode(attributes);
+        if iface.borrow().altnames != altnames {
+            log::trace!("altnames change: {iface:?} now has {altnames:?}");
+            iface.borrow_mut().altnames = altnames;
+        }
+        if iface.borrow().perm_mac != perm_mac_address {
+            iface.borrow_mut().perm_mac = perm_mac_address;
+        }
 
-        Ok((iface.clone(), mac_address))
+        Ok((iface.clone(), mac_address, link_state))
     }
 
-    fn nl_addressmessage_decode(&mut self, msg: &AddressMessage)
+    fn nl_addressmessage_decode(&mut self, conn: usize, msg: &AddressMessage)
                                 -> io::Result<(Rc<RefCell<NetInterface>>, IpAddr)> {
         let AddressMessage{header, attributes, ..} = msg;
 
@@ -186,7 +457,8 @@ impl NetworkSource {
             }
         }
 
-        let iface = match self.iface_cache.entry(header.index) {
+        let key = (self.connections[conn].netns.clone(), header.index);
+        let iface = match self.iface_cache.entry(key) {
             hash_map::Entry::Occupied(entry) => { entry.get().clone() },
             hash_map::Entry::Vacant(_) => {
                 return Err(io::Error::new(io::ErrorKind::InvalidData,
@@ -199,5 +471,349 @@ impl NetworkSource {
             None => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown address")),
         }
     }
+
+    // Returns None for routes we don't report: only unicast routes
+    // from the main table, going through a single known interface
+    fn nl_routemessage_decode(&mut self, conn: usize, msg: &RouteMessage)
+                              -> Option<(Rc<RefCell<NetInterface>>, NetRoute)> {
+        let RouteMessage{header, attributes, ..} = msg;
+
+        if header.kind != route::RouteType::Unicast {
+            return None;
+        }
+
+        // extract fields of interest
+        let mut table = header.table as u32;
+        let mut destination: Option<IpAddr> = None;
+        let mut gateway: Option<IpAddr> = None;
+        let mut source: Option<IpAddr> = None;
+        let mut oif: Option<u32> = None;
+        for nla in attributes {
+            match nla {
+                route::RouteAttribute::Table(id) => table = *id,
+                route::RouteAttribute::Destination(addr) => destination = nl_routeaddress_decode(addr),
+                route::RouteAttribute::Gateway(addr) => gateway = nl_routeaddress_decode(addr),
+                route::RouteAttribute::PrefSource(addr) => source = nl_routeaddress_decode(addr),
+                route::RouteAttribute::Oif(index) => oif = Some(*index),
+                _ => (),
+            }
+        }
+        if table != route::RouteHeader::RT_TABLE_MAIN as u32 {
+            return None;
+        }
+
+        let Some(oif) = oif else {
+            log::debug!("ignoring route with no single output interface: {msg:?}");
+            return None;
+        };
+        let Some(iface) = self.iface_cache.get(&(self.connections[conn].netns.clone(), oif)) else {
+            log::debug!("ignoring route for unknown interface index {oif}");
+            return None;
+        };
+
+        // no destination attribute means a default route
+        let destination = match (destination, header.address_family) {
+            (Some(destination), _) => destination,
+            (None, AddressFamily::Inet) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            (None, AddressFamily::Inet6) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            (None, family) => {
+                log::debug!("ignoring route with address family {family:?}");
+                return None;
+            },
+        };
+
+        Some((iface.clone(), NetRoute { destination,
+                                        prefix_len: header.destination_prefix_length,
+                                        gateway,
+                                        source }))
+    }
 }
 
+// Removal events for what a previous dump had and a new one has not,
+// dropping vanished interfaces from the new state
+fn vanished_events(known_ifaces: Vec<Rc<RefCell<NetInterface>>>,
+                   known_addresses: &NlAddresses,
+                   known_routes: NlRoutes,
+                   current_keys: &HashSet<NetInterfaceKey>,
+                   iface_cache: &mut NetInterfaceCache,
+                   addresses: &mut NlAddresses,
+                   routes: &NlRoutes) -> Vec<NetEvent> {
+    let mut events = Vec::<NetEvent>::new();
+    for iface in known_ifaces {
+        let key = iface.borrow().key();
+        if !current_keys.contains(&key) {
+            log::debug!("resync: {iface:?} disappeared");
+            iface_cache.remove(&key);
+            addresses.remove(&key);
+            events.push(NetEvent{iface, op: NetEventOp::RmIface});
+            continue;
+        }
+        let empty_address_set = HashSet::new();
+        let known_iface_addresses = known_addresses.get(&key).unwrap_or(&empty_address_set);
+        let current_iface_addresses = addresses.get(&key).unwrap_or(&empty_address_set);
+        for address in known_iface_addresses.difference(current_iface_addresses) {
+            events.push(NetEvent{iface: iface.clone(), op: NetEventOp::RmIp(*address)});
+        }
+    }
+    // those of vanished interfaces go away with them
+    for (key, route) in known_routes {
+        if routes.contains_key(&key) {
+            continue;
+        }
+        if let Some(iface) = iface_cache.get(&key.0) {
+            events.push(NetEvent{iface: iface.clone(), op: NetEventOp::RmRoute(route)});
+        }
+    }
+    events
+}
+
+// the agent's own namespace, and the selected named ones
+fn nl_connect_all(selection: &NetNamespaceSelection) -> io::Result<Vec<NlConnection>> {
+    let (handle, messages) = nl_connect()?;
+    let mut connections = vec![NlConnection { netns: None, handle, messages }];
+    for name in netns_names(selection) {
+        // containers may not be started yet, or gone
+        match nl_connect_netns(&name)? {
+            Ok((handle, messages)) => {
+                log::info!("watching network namespace {name}");
+                connections.push(NlConnection { netns: Some(name), handle, messages });
+            },
+            Err(e) => log::warn!("cannot watch network namespace {name}: {e}"),
+        }
+    }
+    Ok(connections)
+}
+
+// the selected named namespaces that currently exist
+fn netns_names(selection: &NetNamespaceSelection) -> Vec<String> {
+    match selection {
+        NetNamespaceSelection::None => vec![],
+        NetNamespaceSelection::Named(names) => names.iter()
+            .filter(|name| Path::new(NETNS_RUN_DIR).join(name).exists())
+            .cloned()
+            .collect(),
+        NetNamespaceSelection::All => match fs::read_dir(NETNS_RUN_DIR) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect(),
+            Err(e) => {
+                log::debug!("cannot list {NETNS_RUN_DIR}: {e}");
+                vec![]
+            },
+        },
+    }
+}
+
+// A netlink socket stays in the namespace it was created in, so we
+// only need to enter the namespace while creating it.  The outer error
+// is about getting back to our own namespace, which leaves this thread
+// in the wrong one.
+fn nl_connect_netns(name: &str) -> io::Result<io::Result<(NlHandle, NlMessages)>> {
+    let own_netns = fs::File::open("/proc/thread-self/ns/net")?;
+    let netns = match fs::File::open(Path::new(NETNS_RUN_DIR).join(name)) {
+        Ok(netns) => netns,
+        Err(e) => return Ok(Err(e)),
+    };
+    if let Err(e) = setns(&netns) {
+        return Ok(Err(e));
+    }
+    let result = nl_connect();
+    // anything else running on this thread would be confused
+    setns(&own_netns).map_err(|e| io::Error::new(
+        e.kind(), format!("cannot get back to own network namespace: {e}")))?;
+    Ok(result)
+}
+
+fn setns(netns: &fs::File) -> io::Result<()> {
+    if unsafe { libc::setns(netns.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// next message from any connection, with the index of the connection;
+// None as soon as one of them is closed
+async fn nl_next_message(connections: &mut [NlConnection])
+                         -> Option<(usize, NetlinkMessage<RouteNetlinkMessage>)> {
+    futures::future::poll_fn(|cx| {
+        for (conn, connection) in connections.iter_mut().enumerate() {
+            match connection.messages.poll_next_unpin(cx) {
+                Poll::Ready(Some((message, _))) => return Poll::Ready(Some((conn, message))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => (),
+            }
+        }
+        Poll::Pending
+    }).await
+}
+
+fn nl_connect() -> io::Result<(NlHandle, NlMessages)> {
+    let (mut connection, handle, messages) = new_connection(NETLINK_ROUTE)?;
+    // What kinds of broadcast messages we want to listen for.
+    let nl_mgroup_flags = RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR
+        | RTMGRP_IPV4_ROUTE | RTMGRP_IPV6_ROUTE;
+    let nl_addr = SocketAddr::new(0, nl_mgroup_flags);
+    connection
+        .socket_mut()
+        .socket_mut()
+        .bind(&nl_addr)?;
+    tokio::spawn(connection);
+    Ok((handle, messages))
+}
+
+fn nl_altnames_decode(attributes: &[link::LinkAttribute]) -> Vec<String> {
+    attributes.iter()
+        .filter_map(|nla| match nla {
+            link::LinkAttribute::PropList(props) => Some(props),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|prop| match prop {
+            link::Prop::AltIfName(altname) => Some(altname.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn nl_macaddress_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<String>>().join(":")
+}
+
+fn nl_kind_decode(info_kind: Option<&link::InfoKind>,
+                  link_layer_type: link::LinkLayerType) -> NetInterfaceKind {
+    match (info_kind, link_layer_type) {
+        // no IFLA_INFO_KIND for physical devices
+        (None, link::LinkLayerType::Ether) => NetInterfaceKind::Ethernet,
+        (None, link::LinkLayerType::Loopback) => NetInterfaceKind::Loopback,
+        (None, _) => NetInterfaceKind::Unknown,
+        (Some(link::InfoKind::Bridge), _) => NetInterfaceKind::Bridge,
+        (Some(link::InfoKind::Bond), _) => NetInterfaceKind::Bond,
+        (Some(link::InfoKind::Vlan), _) => NetInterfaceKind::Vlan,
+        (Some(link::InfoKind::Veth), _) => NetInterfaceKind::Veth,
+        // tap devices carry ethernet frames, tun ones IP packets
+        (Some(link::InfoKind::Tun), link::LinkLayerType::Ether) => NetInterfaceKind::Tap,
+        (Some(link::InfoKind::Tun), _) => NetInterfaceKind::Tun,
+        (Some(link::InfoKind::Wireguard), _) => NetInterfaceKind::Wireguard,
+        (Some(link::InfoKind::MacVlan), _) => NetInterfaceKind::Macvlan,
+        (Some(link::InfoKind::Vxlan), _) => NetInterfaceKind::Vxlan,
+        (Some(kind), _) => NetInterfaceKind::Other(kind.to_string()),
+    }
+}
+
+fn nl_operstate_decode(state: &link::State) -> NetOperState {
+    match state {
+        link::State::NotPresent => NetOperState::NotPresent,
+        link::State::Down => NetOperState::Down,
+        link::State::LowerLayerDown => NetOperState::LowerLayerDown,
+        link::State::Testing => NetOperState::Testing,
+        link::State::Dormant => NetOperState::Dormant,
+        link::State::Up => NetOperState::Up,
+        _ => NetOperState::Unknown,
+    }
+}
+
+fn nl_routeaddress_decode(addr: &route::RouteAddress) -> Option<IpAddr> {
+    match addr {
+        route::RouteAddress::Inet(addr) => Some(IpAddr::V4(*addr)),
+        route::RouteAddress::Inet6(addr) => Some(IpAddr::V6(*addr)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastructs::ToolstackNetInterface;
+
+    fn iface(index: u32) -> Rc<RefCell<NetInterface>> {
+        Rc::new(RefCell::new(NetInterface {
+            netns: None,
+            index,
+            name: format!("eth{index}"),
+            kind: NetInterfaceKind::Ethernet,
+            altnames: vec![],
+            perm_mac: None,
+            toolstack_iface: ToolstackNetInterface::None,
+            link_state: None,
+            stats: None,
+        }))
+    }
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn route(destination: &str, prefix_len: u8) -> NetRoute {
+        NetRoute { destination: ip(destination), prefix_len, gateway: None, source: None }
+    }
+
+    fn route_key(iface: &Rc<RefCell<NetInterface>>, route: &NetRoute)
+                 -> (NetInterfaceKey, IpAddr, u8) {
+        (iface.borrow().key(), route.destination, route.prefix_len)
+    }
+
+    // both interfaces known before, as in NetworkSource after a dump
+    fn cache(ifaces: &[&Rc<RefCell<NetInterface>>]) -> NetInterfaceCache {
+        ifaces.iter().map(|iface| (iface.borrow().key(), (*iface).clone())).collect()
+    }
+
+    #[test]
+    fn vanished_iface() {
+        let (eth0, eth1) = (iface(0), iface(1));
+        let mut iface_cache = cache(&[&eth0, &eth1]);
+        let known_addresses = NlAddresses::from([(eth1.borrow().key(),
+                                                  HashSet::from([ip("10.0.0.2")]))]);
+        let known_route = route("10.0.0.0", 24);
+        let known_routes = NlRoutes::from([(route_key(&eth1, &known_route), known_route)]);
+        let current_keys = HashSet::from([eth0.borrow().key()]);
+        // stale entry, as left by the dump filling the new state
+        let mut addresses = NlAddresses::from([(eth1.borrow().key(), HashSet::new())]);
+
+        let events = vanished_events(vec![eth0.clone(), eth1.clone()], &known_addresses,
+                                     known_routes, &current_keys, &mut iface_cache,
+                                     &mut addresses, &NlRoutes::new());
+        // addresses and routes go away with the interface
+        assert_eq!(events.len(), 1);
+        assert!(Rc::ptr_eq(&events[0].iface, &eth1));
+        assert!(matches!(events[0].op, NetEventOp::RmIface));
+        assert!(!iface_cache.contains_key(&eth1.borrow().key()));
+        assert!(iface_cache.contains_key(&eth0.borrow().key()));
+        assert!(addresses.is_empty());
+    }
+
+    #[test]
+    fn vanished_address() {
+        let eth0 = iface(0);
+        let mut iface_cache = cache(&[&eth0]);
+        let known_addresses = NlAddresses::from([(eth0.borrow().key(),
+                                                  HashSet::from([ip("10.0.0.1"),
+                                                                 ip("fe80::1")]))]);
+        let current_keys = HashSet::from([eth0.borrow().key()]);
+        let mut addresses = NlAddresses::from([(eth0.borrow().key(),
+                                                HashSet::from([ip("10.0.0.1")]))]);
+
+        let events = vanished_events(vec![eth0.clone()], &known_addresses, NlRoutes::new(),
+                                     &current_keys, &mut iface_cache,
+                                     &mut addresses, &NlRoutes::new());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].op, NetEventOp::RmIp(address) if address == ip("fe80::1")));
+    }
+
+    #[test]
+    fn vanished_route() {
+        let eth0 = iface(0);
+        let mut iface_cache = cache(&[&eth0]);
+        let (default, other) = (route("0.0.0.0", 0), route("192.168.0.0", 16));
+        let known_routes = NlRoutes::from([(route_key(&eth0, &default), default.clone()),
+                                           (route_key(&eth0, &other), other.clone())]);
+        let current_keys = HashSet::from([eth0.borrow().key()]);
+        let routes = NlRoutes::from([(route_key(&eth0, &default), default)]);
+
+        let events = vanished_events(vec![eth0.clone()], &NlAddresses::new(), known_routes,
+                                     &current_keys, &mut iface_cache,
+                                     &mut NlAddresses::new(), &routes);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0].op, NetEventOp::RmRoute(route) if *route == other));
+    }
+}
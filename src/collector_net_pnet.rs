@@ -1,4 +1,7 @@
-use crate::datastructs::{NetEvent, NetEventOp, NetInterface, NetInterfaceCache};
+use crate::datastructs::{ChangeThreshold, NetCollectorConfig, NetEvent, NetEventOp, NetInterface,
+                         NetInterfaceCache, NetInterfaceKind, NetLinkState,
+                         NetNamespaceSelection, NetOperState, NetStats};
+use crate::periodic::JitteredInterval;
 use async_stream::try_stream;
 use futures::stream::Stream;
 use ipnetwork::IpNetwork;
@@ -7,10 +10,14 @@ use std::cell::RefCell;
 use std::collections::{hash_map, HashMap, HashSet};
 use std::error::Error;
 use std::io;
+#[cfg(target_os = "linux")]
+use std::mem;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
 use std::rc::Rc;
 use std::time::Duration;
-
-const IFACE_PERIOD_SECONDS: u64 = 60;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use tokio::io::unix::AsyncFd;
 
 #[derive(Debug, Eq, Hash, PartialEq)]
 enum Address {
@@ -22,11 +29,12 @@ struct InterfaceInfo {
     // have a new NetInterface to construct
     name: String,
     addresses: HashSet<Address>,
+    link_state: NetLinkState,
 }
 
 impl InterfaceInfo {
-    pub fn new(name: &str) -> InterfaceInfo {
-        InterfaceInfo { name: name.to_string(), addresses: HashSet::new() }
+    pub fn new(name: &str, link_state: NetLinkState) -> InterfaceInfo {
+        InterfaceInfo { name: name.to_string(), addresses: HashSet::new(), link_state }
     }
 }
 
@@ -34,27 +42,84 @@ type AddressesState = HashMap<u32, InterfaceInfo>;
 pub struct NetworkSource {
     addresses_cache: AddressesState,
     iface_cache: &'static mut NetInterfaceCache,
+    poll_period: Duration,
+    jitter_percent: u8,
+    stats_threshold: ChangeThreshold,
 }
 
 impl NetworkSource {
-    pub fn new(iface_cache: &'static mut NetInterfaceCache) -> io::Result<NetworkSource> {
-        Ok(NetworkSource {addresses_cache: AddressesState::new(), iface_cache})
+    pub fn new(iface_cache: &'static mut NetInterfaceCache,
+               config: &NetCollectorConfig) -> io::Result<NetworkSource> {
+        if !matches!(config.netns, NetNamespaceSelection::None) {
+            log::warn!("network namespaces not supported by this collector, ignored");
+        }
+        Ok(NetworkSource {addresses_cache: AddressesState::new(), iface_cache,
+                          poll_period: config.poll_period,
+                          jitter_percent: config.jitter_percent,
+                          stats_threshold: config.stats_threshold})
     }
 
     pub async fn collect_current(&mut self) -> Result<Vec<NetEvent>, Box<dyn Error>> {
         Ok(self.get_ifconfig_data()?)
     }
 
+    // nothing to reconnect, just catch up with what we may have missed
+    pub async fn reconnect(&mut self) -> Result<Vec<NetEvent>, Box<dyn Error>> {
+        Ok(self.get_ifconfig_data()?)
+    }
+
     pub fn stream(&mut self) -> impl Stream<Item = io::Result<NetEvent>> + '_ {
         try_stream! {
-            let mut interval = tokio::time::interval(Duration::from_secs(IFACE_PERIOD_SECONDS));
+            let mut interval = JitteredInterval::new(self.poll_period, self.jitter_percent);
+            let mut notifier = match ChangeNotifier::new() {
+                Ok(notifier) => Some(notifier),
+                Err(e) => {
+                    log::warn!("no network change notifications, only polling: {e}");
+                    None
+                },
+            };
             loop {
-                interval.tick().await;
+                match &notifier {
+                    Some(active_notifier) => tokio::select! {
+                        _ = interval.tick() => (),
+                        changed = active_notifier.changed() => match changed {
+                            // next full scan only after a full period
+                            Ok(()) => interval.reset(),
+                            Err(e) => {
+                                log::warn!("network change notifications failed, only polling: {e}");
+                                notifier = None;
+                            },
+                        },
+                    },
+                    None => { interval.tick().await; },
+                }
                 for net_event in self.get_ifconfig_data()? {
                      yield net_event;
                 }
+                for net_event in self.get_stats_data() {
+                     yield net_event;
+                }
+            }
+        }
+    }
+
+    // pnet does not give access to counters, get them from the OS
+    fn get_stats_data(&self) -> Vec<NetEvent> {
+        let mut events = vec![];
+        for iface in self.iface_cache.values() {
+            let iface_name = iface.borrow().name.clone();
+            match get_iface_stats(&iface_name) {
+                Ok(stats) => {
+                    if iface.borrow_mut().update_stats(stats, &self.stats_threshold) {
+                        events.push(NetEvent{iface: iface.clone(), op: NetEventOp::Stats(stats)});
+                    }
+                },
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => break,
+                // interface may have disappeared since last scan
+                Err(e) => log::debug!("cannot get stats for {iface_name}: {e}"),
             }
         }
+        events
     }
 
     fn get_ifconfig_data(&mut self) -> io::Result<Vec<NetEvent>> {
@@ -67,7 +132,7 @@ impl NetworkSource {
             let name = iface.name.split(":").next().unwrap_or(&iface.name);
             let entry = current_addresses
                 .entry(iface.index)
-                .or_insert_with(|| InterfaceInfo::new(name));
+                .or_insert_with(|| InterfaceInfo::new(name, link_state_from_pnet(iface)));
             for ip in &iface.ips {
                 entry.addresses.insert(Address::IP(*ip));
             }
@@ -86,7 +151,7 @@ impl NetworkSource {
         // disappearing addresses
         for (cached_iface_index, cached_info) in self.addresses_cache.iter() {
             // iface object from iface_cache
-            let iface = match self.iface_cache.entry(*cached_iface_index) {
+            let iface = match self.iface_cache.entry((None, *cached_iface_index)) {
                 hash_map::Entry::Occupied(entry) => { entry.get().clone() },
                 hash_map::Entry::Vacant(_) => {
                     return Err(io::Error::new(
@@ -118,14 +183,27 @@ impl NetworkSource {
         // appearing addresses
         for (iface_index, iface_info) in current_addresses.iter() {
             let iface = self.iface_cache
-                .entry(*iface_index)
-                .or_insert_with_key(|index| {
+                .entry((None, *iface_index))
+                .or_insert_with_key(|(_, index)| {
+                    let mac = iface_info.addresses.iter().find_map(|address| match address {
+                        Address::MAC(mac) => Some(mac.to_string()),
+                        _ => None,
+                    });
+                    let kind = get_iface_kind(&iface_info.name, mac.is_some());
                     let iface = Rc::new(RefCell::new(
-                        NetInterface::new(*index, Some(iface_info.name.clone()))));
+                        NetInterface::new(None, *index, Some(iface_info.name.clone()), mac.as_deref(),
+                                          kind)));
                     events.push(NetEvent{iface: iface.clone(), op: NetEventOp::AddIface});
                     iface
                 })
                 .clone();
+            // only notify link state when it changed
+            let link_changed = iface.borrow().link_state != Some(iface_info.link_state);
+            if link_changed {
+                iface.borrow_mut().link_state = Some(iface_info.link_state);
+                events.push(NetEvent{iface: iface.clone(),
+                                     op: NetEventOp::LinkState(iface_info.link_state)});
+            }
             let cache_adresses =
                 if let Some(cache_info) = self.addresses_cache.get(iface_index) {
                     &cache_info.addresses
@@ -149,3 +227,227 @@ impl NetworkSource {
         Ok(events)
     }
 }
+
+// Kernel notification of network changes, only used to wake up early:
+// what changed is still found by a full scan
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+struct ChangeNotifier {
+    socket: AsyncFd<OwnedFd>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+impl ChangeNotifier {
+    #[cfg(target_os = "linux")]
+    fn new() -> io::Result<ChangeNotifier> {
+        let fd = unsafe { libc::socket(libc::AF_NETLINK,
+                                       libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                                       libc::NETLINK_ROUTE) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR
+                          | libc::RTMGRP_IPV6_IFADDR) as u32;
+        if unsafe { libc::bind(socket.as_raw_fd(),
+                               &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                               mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = AsyncFd::new(socket)?;
+        Ok(ChangeNotifier { socket })
+    }
+
+    #[cfg(target_os = "freebsd")]
+    fn new() -> io::Result<ChangeNotifier> {
+        // a routing socket gets all routing, address and link changes
+        let fd = unsafe { libc::socket(libc::PF_ROUTE,
+                                       libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                                       libc::AF_UNSPEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+        let socket = AsyncFd::new(socket)?;
+        Ok(ChangeNotifier { socket })
+    }
+
+    // wait for a change, and consume all notifications pending
+    async fn changed(&self) -> io::Result<()> {
+        let mut guard = self.socket.readable().await?;
+        let mut buf = [0u8; 4096];
+        loop {
+            let ret = unsafe { libc::recv(self.socket.as_raw_fd(),
+                                          buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if ret >= 0 {
+                continue;
+            }
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EAGAIN) => {
+                    guard.clear_ready();
+                    return Ok(());
+                },
+                Some(libc::EINTR) => (),
+                // lost notifications do not matter, we rescan anyway
+                Some(libc::ENOBUFS) => (),
+                _ => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+struct ChangeNotifier {}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+impl ChangeNotifier {
+    fn new() -> io::Result<ChangeNotifier> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    async fn changed(&self) -> io::Result<()> {
+        futures::future::pending().await
+    }
+}
+
+// pnet only gives us interface flags, so the operational state is
+// approximated from IFF_UP and IFF_RUNNING
+fn link_state_from_pnet(iface: &pnet_datalink::NetworkInterface) -> NetLinkState {
+    let admin_up = iface.is_up();
+    let carrier = iface.is_running();
+    let oper = match (admin_up, carrier) {
+        (false, _) => NetOperState::Down,
+        (true, true) => NetOperState::Up,
+        (true, false) => NetOperState::LowerLayerDown,
+    };
+    NetLinkState { admin_up, carrier, oper }
+}
+
+// virtual devices may declare their type in sysfs, others need some
+// guessing
+#[cfg(target_os = "linux")]
+fn get_iface_kind(iface_name: &str, has_mac: bool) -> NetInterfaceKind {
+    let sysfs_path = format!("/sys/class/net/{iface_name}");
+    if let Ok(uevent) = std::fs::read_to_string(format!("{sysfs_path}/uevent")) {
+        if let Some(devtype) = uevent.lines().find_map(|line| line.strip_prefix("DEVTYPE=")) {
+            return match devtype {
+                "bridge" => NetInterfaceKind::Bridge,
+                "bond" => NetInterfaceKind::Bond,
+                "vlan" => NetInterfaceKind::Vlan,
+                "wireguard" => NetInterfaceKind::Wireguard,
+                "vxlan" => NetInterfaceKind::Vxlan,
+                other => NetInterfaceKind::Other(other.to_string()),
+            };
+        }
+    }
+    if let Ok(tun_flags) = std::fs::read_to_string(format!("{sysfs_path}/tun_flags")) {
+        let tun_flags = i32::from_str_radix(tun_flags.trim().trim_start_matches("0x"), 16)
+            .unwrap_or(0);
+        return if tun_flags & libc::IFF_TAP != 0 {
+            NetInterfaceKind::Tap
+        } else {
+            NetInterfaceKind::Tun
+        };
+    }
+    match std::fs::read_to_string(format!("{sysfs_path}/type")).as_deref().map(str::trim) {
+        Ok("772") => NetInterfaceKind::Loopback, // ARPHRD_LOOPBACK
+        _ if iface_name.starts_with("veth") => NetInterfaceKind::Veth,
+        _ if has_mac => NetInterfaceKind::Ethernet,
+        _ => NetInterfaceKind::Unknown,
+    }
+}
+
+// only the conventional names of cloned interfaces can tell
+#[cfg(target_os = "freebsd")]
+fn get_iface_kind(iface_name: &str, has_mac: bool) -> NetInterfaceKind {
+    let driver = iface_name.trim_end_matches(|c: char| c.is_ascii_digit());
+    match driver {
+        "lo" => NetInterfaceKind::Loopback,
+        "bridge" => NetInterfaceKind::Bridge,
+        "lagg" => NetInterfaceKind::Bond,
+        "vlan" => NetInterfaceKind::Vlan,
+        "tun" => NetInterfaceKind::Tun,
+        "tap" => NetInterfaceKind::Tap,
+        "wg" => NetInterfaceKind::Wireguard,
+        "vxlan" => NetInterfaceKind::Vxlan,
+        _ if iface_name.starts_with("epair") => NetInterfaceKind::Veth,
+        _ if iface_name.contains('.') => NetInterfaceKind::Vlan,
+        _ if has_mac => NetInterfaceKind::Ethernet,
+        _ => NetInterfaceKind::Unknown,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+fn get_iface_kind(_iface_name: &str, has_mac: bool) -> NetInterfaceKind {
+    if has_mac {
+        NetInterfaceKind::Ethernet
+    } else {
+        NetInterfaceKind::Unknown
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get_iface_stats(iface_name: &str) -> io::Result<NetStats> {
+    let read_counter = |counter: &str| -> io::Result<u64> {
+        let path = format!("/sys/class/net/{iface_name}/statistics/{counter}");
+        std::fs::read_to_string(&path)?
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {e}")))
+    };
+    Ok(NetStats {
+        rx_bytes: read_counter("rx_bytes")?,
+        rx_packets: read_counter("rx_packets")?,
+        rx_errors: read_counter("rx_errors")?,
+        rx_dropped: read_counter("rx_dropped")?,
+        tx_bytes: read_counter("tx_bytes")?,
+        tx_packets: read_counter("tx_packets")?,
+        tx_errors: read_counter("tx_errors")?,
+        tx_dropped: read_counter("tx_dropped")?,
+    })
+}
+
+// counters are in the `if_data` attached to the AF_LINK entry
+#[cfg(target_os = "freebsd")]
+fn get_iface_stats(iface_name: &str) -> io::Result<NetStats> {
+    use std::ffi::CStr;
+    let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut ifap) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut stats = None;
+    let mut ifa = ifap;
+    while !ifa.is_null() {
+        let entry = unsafe { &*ifa };
+        ifa = entry.ifa_next;
+        if entry.ifa_addr.is_null() || entry.ifa_data.is_null()
+            || unsafe { (*entry.ifa_addr).sa_family } as i32 != libc::AF_LINK {
+            continue;
+        }
+        if unsafe { CStr::from_ptr(entry.ifa_name) }.to_bytes() != iface_name.as_bytes() {
+            continue;
+        }
+        let data = unsafe { &*(entry.ifa_data as *const libc::if_data) };
+        stats = Some(NetStats {
+            rx_bytes: data.ifi_ibytes,
+            rx_packets: data.ifi_ipackets,
+            rx_errors: data.ifi_ierrors,
+            rx_dropped: data.ifi_iqdrops,
+            tx_bytes: data.ifi_obytes,
+            tx_packets: data.ifi_opackets,
+            tx_errors: data.ifi_oerrors,
+            tx_dropped: data.ifi_oqdrops,
+        });
+        break;
+    }
+    unsafe { libc::freeifaddrs(ifap) };
+    stats.ok_or(io::Error::new(io::ErrorKind::NotFound,
+                               format!("no link-level data for {iface_name}")))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+fn get_iface_stats(_iface_name: &str) -> io::Result<NetStats> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for iface stats"))
+}
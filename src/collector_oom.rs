@@ -0,0 +1,19 @@
+use crate::datastructs::OomInfo;
+use futures::stream::Stream;
+use std::io;
+
+pub struct OomSource {}
+
+impl OomSource {
+    pub fn new() -> io::Result<OomSource> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for OOM events"))
+    }
+
+    pub fn collect_current(&self) -> io::Result<OomInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for OOM events"))
+    }
+
+    pub fn stream(&self) -> impl Stream<Item = io::Result<OomInfo>> + '_ {
+        futures::stream::pending::<io::Result<OomInfo>>()
+    }
+}
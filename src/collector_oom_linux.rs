@@ -0,0 +1,227 @@
+use crate::datastructs::{OomInfo, OomKill};
+use async_stream::try_stream;
+use futures::stream::Stream;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::unix::AsyncFd;
+
+// Kills are counted in /proc/vmstat.  Their victim is only told in
+// the kernel's OOM report, cgroup v2 memory.events having counters
+// only, so we read it from /dev/kmsg as soon as it is logged: the
+// task dump preceding it can otherwise push it out of the ring buffer.
+
+const PROC_VMSTAT: &str = "/proc/vmstat";
+// where the kernel logs its OOM reports
+const DEV_KMSG: &str = "/dev/kmsg";
+// one record per read, longer ones get truncated
+const KMSG_RECORD_MAX: usize = 8192;
+
+pub struct OomSource {
+    // None when we cannot read kernel messages, kills are then only
+    // counted
+    kmsg: Option<AsyncFd<fs::File>>,
+    state: RefCell<OomState>,
+}
+
+#[derive(Default)]
+struct OomState {
+    oom_info: OomInfo,
+    // from the "oom-kill:" record preceding "Killed process"
+    pending_cgroup: Option<(u32, String)>,
+    kmsg_failed: bool,
+}
+
+impl OomSource {
+    // fails with NotFound when the kernel does not count kills (< 4.13)
+    pub fn new() -> io::Result<OomSource> {
+        let kills = read_vmstat_kills()?;
+        let kmsg = match open_kmsg() {
+            Ok(kmsg) => Some(kmsg),
+            Err(e) => {
+                log::info!("cannot read {DEV_KMSG}, OOM victims will not be reported: {e}");
+                None
+            },
+        };
+        let oom_info = OomInfo { kills, last_kill: None };
+        Ok(OomSource { kmsg, state: RefCell::new(OomState { oom_info, ..Default::default() }) })
+    }
+
+    pub fn collect_current(&self) -> io::Result<OomInfo> {
+        self.read_kmsg();
+        let kills = read_vmstat_kills()?;
+        let mut state = self.state.borrow_mut();
+        if kills > state.oom_info.kills {
+            match &state.oom_info.last_kill {
+                Some(last_kill) =>
+                    log::warn!("OOM killer fired {} time(s), last on {} ({}) in cgroup {}",
+                               kills - state.oom_info.kills, last_kill.pid, last_kill.process,
+                               last_kill.cgroup.as_deref().unwrap_or("unknown")),
+                None => log::warn!("OOM killer fired {} time(s)", kills - state.oom_info.kills),
+            }
+        }
+        state.oom_info.kills = kills;
+        Ok(state.oom_info.clone())
+    }
+
+    // fresh values each time the kernel reports a victim
+    pub fn stream(&self) -> impl Stream<Item = io::Result<OomInfo>> + '_ {
+        try_stream! {
+            if let Some(kmsg) = &self.kmsg {
+                while !self.state.borrow().kmsg_failed {
+                    let mut guard = kmsg.readable().await?;
+                    let new_victim = self.read_kmsg();
+                    // drained up to EAGAIN, or given up
+                    guard.clear_ready();
+                    if new_victim {
+                        yield self.collect_current()?;
+                    }
+                }
+            }
+            futures::future::pending::<()>().await;
+        }
+    }
+
+    // drain what the kernel logged since last time, telling whether
+    // there was a new victim
+    fn read_kmsg(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        let Some(kmsg) = &self.kmsg else {
+            return false;
+        };
+        if state.kmsg_failed {
+            return false;
+        }
+        let mut new_victim = false;
+        let mut buf = vec![0u8; KMSG_RECORD_MAX];
+        loop {
+            let len = match kmsg.get_ref().read(&mut buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return new_victim,
+                // records overwritten before we read them, we get the
+                // oldest remaining one next
+                Err(e) if e.raw_os_error() == Some(libc::EPIPE) => {
+                    log::debug!("missed kernel messages, an OOM kill may go unreported");
+                    continue;
+                },
+                Err(e) => {
+                    log::warn!("cannot read {DEV_KMSG}, OOM victims will not be reported any more: {e}");
+                    state.kmsg_failed = true;
+                    return new_victim;
+                },
+            };
+            if len == 0 {
+                return new_victim;
+            }
+            let record = String::from_utf8_lossy(&buf[..len]);
+            match parse_kmsg_record(&record) {
+                Some((_, KmsgOom::Cgroup(pid, cgroup))) =>
+                    state.pending_cgroup = Some((pid, cgroup)),
+                Some((timestamp_us, KmsgOom::Killed(pid, process))) => {
+                    let cgroup = mem::take(&mut state.pending_cgroup)
+                        .filter(|(cgroup_pid, _)| *cgroup_pid == pid)
+                        .map(|(_, cgroup)| cgroup);
+                    state.oom_info.last_kill = Some(OomKill { time: boot_time_to_epoch(timestamp_us),
+                                                              pid, process, cgroup });
+                    new_victim = true;
+                },
+                None => (),
+            }
+        }
+    }
+}
+
+fn read_vmstat_kills() -> io::Result<u64> {
+    let vmstat = fs::read_to_string(PROC_VMSTAT)?;
+    let Some(kills) = vmstat.lines().find_map(|line| line.strip_prefix("oom_kill ")) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound,
+                                  format!("no oom_kill counter in {PROC_VMSTAT}")));
+    };
+    kills.trim().parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData,
+                                    format!("parsing oom_kill in {PROC_VMSTAT}: {e}")))
+}
+
+// only messages logged from now on
+fn open_kmsg() -> io::Result<AsyncFd<fs::File>> {
+    let mut kmsg = fs::OpenOptions::new().read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(DEV_KMSG)?;
+    kmsg.seek(SeekFrom::End(0))?;
+    AsyncFd::new(kmsg)
+}
+
+#[derive(Debug, PartialEq)]
+enum KmsgOom {
+    // task_memcg of an "oom-kill:" record (kernel >= 4.19), by pid
+    Cgroup(u32, String),
+    // pid and command of the victim
+    Killed(u32, String),
+}
+
+// "<prio>,<seq>,<usecs since boot>,<flags>[,...];<message>", possibly
+// followed by continuation lines
+fn parse_kmsg_record(record: &str) -> Option<(u64, KmsgOom)> {
+    let (header, message) = record.split_once(';')?;
+    let timestamp_us = header.split(',').nth(2)?.parse().ok()?;
+    let message = message.lines().next()?;
+    if let Some(fields) = message.strip_prefix("oom-kill:") {
+        let field = |name: &str| fields.split(',').find_map(|field| field.strip_prefix(name));
+        let pid = field("pid=")?.parse().ok()?;
+        return Some((timestamp_us, KmsgOom::Cgroup(pid, field("task_memcg=")?.to_string())));
+    }
+    // "Out of memory: Killed process 1234 (stress) total-vm:...", or
+    // "Memory cgroup out of memory: ..."
+    let (_, killed) = message.split_once("Killed process ")?;
+    let (pid, rest) = killed.split_once(" (")?;
+    let process = rest.split_once(") ").map(|(process, _)| process)
+        .or_else(|| rest.strip_suffix(')'))?;
+    Some((timestamp_us, KmsgOom::Killed(pid.parse().ok()?, process.to_string())))
+}
+
+// kernel message timestamps are on the monotonic clock
+fn boot_time_to_epoch(timestamp_us: u64) -> u64 {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let now_us = now.tv_sec as u64 * 1_000_000 + now.tv_nsec as u64 / 1000;
+    let epoch_now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (epoch_now.as_micros() as u64).saturating_sub(now_us.saturating_sub(timestamp_us)) / 1_000_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmsg_oom_records() {
+        let record = "3,1234,5678901234,-;oom-kill:constraint=CONSTRAINT_MEMCG,\
+                      nodemask=(null),cpuset=/,mems_allowed=0,\
+                      oom_memcg=/system.slice/db.service,\
+                      task_memcg=/system.slice/db.service,task=postgres,pid=4321,uid=70\n";
+        assert_eq!(parse_kmsg_record(record),
+                   Some((5678901234,
+                         KmsgOom::Cgroup(4321, "/system.slice/db.service".to_string()))));
+        let record = "3,1235,5678901300,-;Memory cgroup out of memory: Killed process 4321 \
+                      (postgres) total-vm:1024kB, anon-rss:512kB, file-rss:0kB, \
+                      shmem-rss:0kB, UID:70 pgtables:64kB oom_score_adj:0\n";
+        assert_eq!(parse_kmsg_record(record),
+                   Some((5678901300, KmsgOom::Killed(4321, "postgres".to_string()))));
+        // comm may contain spaces, older kernels end the line there
+        let record = "3,7,100,-;Killed process 42 (Web Content)\n SUBSYSTEM=foo\n";
+        assert_eq!(parse_kmsg_record(record),
+                   Some((100, KmsgOom::Killed(42, "Web Content".to_string()))));
+    }
+
+    #[test]
+    fn kmsg_other_records() {
+        assert_eq!(parse_kmsg_record("6,1,2,-;eth0: link up\n"), None);
+        assert_eq!(parse_kmsg_record("no header at all"), None);
+        assert_eq!(parse_kmsg_record("3,1,x,-;Killed process 42 (foo)\n"), None);
+        assert_eq!(parse_kmsg_record("3,1,2,-;Killed process x (foo)\n"), None);
+        // no pid to match it with
+        assert_eq!(parse_kmsg_record("3,1,2,-;oom-kill:task_memcg=/,task=foo\n"), None);
+    }
+}
@@ -0,0 +1,19 @@
+use crate::datastructs::{PressureInfo, PsiTrigger};
+use futures::stream::Stream;
+use std::io;
+
+pub struct PressureSource {}
+
+impl PressureSource {
+    pub fn new(_triggers: &[PsiTrigger]) -> io::Result<PressureSource> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for pressure"))
+    }
+
+    pub fn collect_current(&self) -> io::Result<PressureInfo> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no implementation for pressure"))
+    }
+
+    pub fn stream(&self) -> impl Stream<Item = io::Result<PressureInfo>> + '_ {
+        futures::stream::pending::<io::Result<PressureInfo>>()
+    }
+}
@@ -0,0 +1,149 @@
+use crate::datastructs::{PressureInfo, PsiAverages, PsiResource, PsiResourceInfo, PsiTrigger};
+use async_stream::try_stream;
+use futures::stream::Stream;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+
+const PROC_PRESSURE: &str = "/proc/pressure";
+
+pub struct PressureSource {
+    // kernel signals crossed thresholds as POLLPRI
+    triggers: Vec<AsyncFd<File>>,
+}
+
+impl PressureSource {
+    // fails with NotFound when the kernel has no PSI support, and
+    // Unsupported when it is disabled (psi=0, default on e.g. RHEL 8)
+    pub fn new(triggers: &[PsiTrigger]) -> io::Result<PressureSource> {
+        fs::metadata(PROC_PRESSURE)
+            .map_err(|e| io::Error::new(e.kind(), format!("{PROC_PRESSURE}: {e}")))?;
+        read_pressure()?;
+        let triggers = triggers.iter()
+            .map(register_trigger)
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(PressureSource { triggers })
+    }
+
+    pub fn collect_current(&self) -> io::Result<PressureInfo> {
+        read_pressure()
+    }
+
+    // fresh values each time a trigger fires
+    pub fn stream(&self) -> impl Stream<Item = io::Result<PressureInfo>> + '_ {
+        try_stream! {
+            if self.triggers.is_empty() {
+                futures::future::pending::<()>().await;
+            }
+            loop {
+                {
+                    let (guard, _, _) = futures::future::select_all(
+                        self.triggers.iter()
+                            .map(|trigger| Box::pin(trigger.ready(Interest::PRIORITY)))).await;
+                    guard?.clear_ready();
+                }
+                match self.collect_current() {
+                    Ok(pressure_info) => yield pressure_info,
+                    Err(e) => log::warn!("cannot read pressure stall information: {e}"),
+                }
+            }
+        }
+    }
+}
+
+fn read_pressure() -> io::Result<PressureInfo> {
+    let mut pressure_info = PressureInfo::default();
+    for resource in PsiResource::ALL {
+        let path = format!("{PROC_PRESSURE}/{}", resource.as_str());
+        match fs::read_to_string(&path) {
+            Ok(content) => pressure_info.resources.push((resource, parse_pressure(&content)?)),
+            // e.g. no io pressure without a block layer
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) =>
+                return Err(io::Error::new(io::ErrorKind::Unsupported,
+                                          format!("{path}: PSI disabled, see psi= boot parameter"))),
+            Err(e) => return Err(io::Error::new(e.kind(), format!("{path}: {e}"))),
+        }
+    }
+    Ok(pressure_info)
+}
+
+fn register_trigger(trigger: &PsiTrigger) -> io::Result<AsyncFd<File>> {
+    let path = format!("{PROC_PRESSURE}/{}", trigger.resource.as_str());
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(&path)?;
+    // the kernel wants the terminating NUL
+    let request = format!("some {} {}\0", trigger.stall_ms * 1000, PsiTrigger::WINDOW_MS * 1000);
+    file.write_all(request.as_bytes())
+        .map_err(|e| io::Error::new(e.kind(), format!("registering trigger on {path}: {e}")))?;
+    log::debug!("registered PSI trigger {trigger:?}");
+    let file = AsyncFd::with_interest(file, Interest::PRIORITY)?;
+    Ok(file)
+}
+
+// "some avg10=0.00 avg60=0.00 avg300=0.00 total=0" line, then a
+// similar "full" one
+fn parse_pressure(content: &str) -> io::Result<PsiResourceInfo> {
+    let mut some = None;
+    let mut full = None;
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        let kind = words.next();
+        let mut averages = PsiAverages::default();
+        for word in words {
+            let parse_error = || io::Error::new(io::ErrorKind::InvalidData,
+                                                format!("cannot parse {word:?} in pressure info"));
+            let (name, value) = word.split_once('=').ok_or_else(parse_error)?;
+            match name {
+                "avg10" => averages.avg10 = value.parse().map_err(|_| parse_error())?,
+                "avg60" => averages.avg60 = value.parse().map_err(|_| parse_error())?,
+                "avg300" => averages.avg300 = value.parse().map_err(|_| parse_error())?,
+                "total" => averages.total_us = value.parse().map_err(|_| parse_error())?,
+                _ => (),
+            }
+        }
+        match kind {
+            Some("some") => some = Some(averages),
+            Some("full") => full = Some(averages),
+            _ => (),
+        }
+    }
+    let some = some.ok_or(io::Error::new(io::ErrorKind::InvalidData,
+                                         "no \"some\" line in pressure info"))?;
+    Ok(PsiResourceInfo { some, full })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pressure() {
+        let info = parse_pressure("some avg10=1.50 avg60=0.75 avg300=0.10 total=123456\n\
+                                   full avg10=0.20 avg60=0.00 avg300=0.00 total=789\n")
+            .unwrap();
+        assert_eq!(info, PsiResourceInfo {
+            some: PsiAverages { avg10: 1.5, avg60: 0.75, avg300: 0.1, total_us: 123456 },
+            full: Some(PsiAverages { avg10: 0.2, avg60: 0.0, avg300: 0.0, total_us: 789 }),
+        });
+        // cpu before Linux 5.13, unknown fields ignored
+        let info = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 avg900=0.00 total=5\n")
+            .unwrap();
+        assert_eq!(info.full, None);
+        assert_eq!(info.some.total_us, 5);
+    }
+
+    #[test]
+    fn bad_pressure() {
+        assert!(parse_pressure("").is_err());
+        assert!(parse_pressure("full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").is_err());
+        assert!(parse_pressure("some avg10=x avg60=0.00 avg300=0.00 total=0\n").is_err());
+        assert!(parse_pressure("some avg10 avg60=0.00 avg300=0.00 total=0\n").is_err());
+        assert!(parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=-1\n").is_err());
+    }
+}
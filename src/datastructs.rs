@@ -1,14 +1,428 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::net::IpAddr;
 use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
 
 pub struct KernelInfo {
     pub release: String,
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HostnameInfo {
+    pub hostname: String,
+    pub fqdn: Option<String>,   // None if it cannot be resolved
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DnsInfo {
+    pub nameservers: Vec<String>,
+    pub search: Vec<String>,
+}
+
+// Minimum change of a value worth a Xenstore write
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeThreshold {
+    Absolute(u64),
+    Percent(f64),               // of the previous value
+}
+
+impl ChangeThreshold {
+    pub fn exceeded(&self, old: u64, new: u64) -> bool {
+        let delta = old.abs_diff(new);
+        delta > 0 && match self {
+            ChangeThreshold::Absolute(min) => delta >= *min,
+            ChangeThreshold::Percent(percent) => delta as f64 * 100.0 >= old as f64 * percent,
+        }
+    }
+}
+
+impl Default for ChangeThreshold {
+    // any change
+    fn default() -> ChangeThreshold {
+        ChangeThreshold::Absolute(0)
+    }
+}
+
+// "<value>" or "<percent>%"
+impl FromStr for ChangeThreshold {
+    type Err = String;
+    fn from_str(s: &str) -> Result<ChangeThreshold, String> {
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.parse::<f64>() {
+                Ok(percent) if percent >= 0.0 => Ok(ChangeThreshold::Percent(percent)),
+                _ => Err(format!("{percent:?} is not a valid percentage")),
+            },
+            None => s.parse().map(ChangeThreshold::Absolute)
+                .map_err(|e| format!("cannot parse {s:?} as a threshold: {e}")),
+        }
+    }
+}
+
+// Memory usage breakdown, in KiB unless noted.  Fields not provided
+// by the OS are None.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemInfo {
+    pub total_kb: usize,
+    pub available_kb: usize,
+    pub free_kb: Option<usize>,
+    pub buffers_kb: Option<usize>,
+    pub cached_kb: Option<usize>,
+    pub shmem_kb: Option<usize>,
+    pub slab_kb: Option<usize>,
+    pub committed_kb: Option<usize>,    // allocated, whether used or not
+    pub swap_total_kb: Option<usize>,
+    pub swap_free_kb: Option<usize>,
+    pub swap_in_kb_per_s: Option<usize>, // since the previous sample
+    pub swap_out_kb_per_s: Option<usize>,
+    pub swap_devices: Vec<SwapDevice>,
+    pub hugepages_total: Option<usize>, // in pages of hugepage_size_kb
+    pub hugepages_free: Option<usize>,
+    pub hugepages_reserved: Option<usize>,
+    pub hugepages_surplus: Option<usize>,
+    pub hugepage_size_kb: Option<usize>,
+}
+
+impl MemInfo {
+    // (name, value) pairs of known fields, for publishers
+    pub fn fields(&self) -> Vec<(&'static str, usize)> {
+        [("total", Some(self.total_kb)),
+         ("available", Some(self.available_kb)),
+         ("free", self.free_kb),
+         ("buffers", self.buffers_kb),
+         ("cached", self.cached_kb),
+         ("shmem", self.shmem_kb),
+         ("slab", self.slab_kb),
+         ("committed", self.committed_kb),
+         ("swap/total", self.swap_total_kb),
+         ("swap/free", self.swap_free_kb),
+         ("swap/in-rate", self.swap_in_kb_per_s),
+         ("swap/out-rate", self.swap_out_kb_per_s),
+         ("hugepages/total", self.hugepages_total),
+         ("hugepages/free", self.hugepages_free),
+         ("hugepages/reserved", self.hugepages_reserved),
+         ("hugepages/surplus", self.hugepages_surplus),
+         ("hugepages/size", self.hugepage_size_kb)]
+            .iter()
+            .filter_map(|(name, value)| value.map(|value| (*name, value)))
+            .collect()
+    }
+
+    // the threshold is in KiB, other fields must not change at all
+    pub fn changed_beyond(&self, other: &MemInfo, threshold: &ChangeThreshold) -> bool {
+        let in_kb = |name: &str| (!name.starts_with("hugepages/") || name == "hugepages/size")
+            && !name.ends_with("-rate");
+        let (fields, other_fields) = (self.fields(), other.fields());
+        fields.len() != other_fields.len() ||
+            fields.iter().zip(other_fields.iter()).any(
+                |((name, value), (other_name, other_value))|
+                name != other_name || if in_kb(name) {
+                    threshold.exceeded(*other_value as u64, *value as u64)
+                } else {
+                    value != other_value
+                })
+            || self.swap_devices.len() != other.swap_devices.len()
+            || self.swap_devices.iter().zip(other.swap_devices.iter()).any(
+                |(device, other_device)| device.changed_beyond(other_device, threshold))
+    }
+}
+
+// swap partition, file or compressed RAM device, sizes in KiB
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapDevice {
+    pub name: String,
+    // "partition", "file", "zram"...
+    pub kind: String,
+    pub size_kb: usize,
+    pub used_kb: usize,
+    // not all OSes have one
+    pub priority: Option<i32>,
+}
+
+impl SwapDevice {
+    pub fn changed_beyond(&self, other: &SwapDevice, threshold: &ChangeThreshold) -> bool {
+        self.name != other.name
+            || self.kind != other.kind
+            || self.size_kb != other.size_kb
+            || self.priority != other.priority
+            || threshold.exceeded(other.used_kb as u64, self.used_kb as u64)
+    }
+}
+
+// whether the balloon driver gets the guest to the memory target set
+// by the toolstack
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BalloonState {
+    Reached,
+    Moving,
+    // no progress for a while, the target is likely unreachable
+    Stuck,
+}
+
+impl BalloonState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BalloonState::Reached => "reached",
+            BalloonState::Moving => "moving",
+            BalloonState::Stuck => "stuck",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BalloonInfo {
+    pub current_kb: usize,
+    pub target_kb: usize,
+    pub state: BalloonState,
+}
+
+impl BalloonInfo {
+    pub fn changed_beyond(&self, other: &BalloonInfo, threshold: &ChangeThreshold) -> bool {
+        self.state != other.state
+            || self.target_kb != other.target_kb
+            || threshold.exceeded(other.current_kb as u64, self.current_kb as u64)
+    }
+}
+
+// resources tracked by pressure stall information (PSI)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PsiResource {
+    Cpu,
+    Memory,
+    Io,
+}
+
+impl PsiResource {
+    pub const ALL: [PsiResource; 3] = [PsiResource::Cpu, PsiResource::Memory, PsiResource::Io];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PsiResource::Cpu => "cpu",
+            PsiResource::Memory => "memory",
+            PsiResource::Io => "io",
+        }
+    }
+}
+
+// share of time tasks were stalled, as percentages averaged over 10 s,
+// 60 s and 300 s, and cumulated stall time
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PsiAverages {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    pub total_us: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PsiResourceInfo {
+    // some tasks stalled
+    pub some: PsiAverages,
+    // all non-idle tasks stalled, not reported for cpu by older kernels
+    pub full: Option<PsiAverages>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PressureInfo {
+    pub resources: Vec<(PsiResource, PsiResourceInfo)>,
+}
+
+impl PressureInfo {
+    // whether averages differ at the precision we publish them, the
+    // stall time total changes on any stall
+    pub fn same_averages(&self, other: &PressureInfo) -> bool {
+        let rounded = |pressure: &PressureInfo| -> Vec<i64> {
+            pressure.resources.iter()
+                .flat_map(|(_, info)| [Some(info.some), info.full])
+                .flatten()
+                .flat_map(|averages| [averages.avg10, averages.avg60, averages.avg300])
+                .map(|average| (average * 100.0).round() as i64)
+                .collect()
+        };
+        rounded(self) == rounded(other)
+    }
+}
+
+// Ask to be notified when tasks stall on a resource for more than
+// `stall_ms` within a window
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PsiTrigger {
+    pub resource: PsiResource,
+    pub stall_ms: u32,
+}
+
+impl PsiTrigger {
+    pub const WINDOW_MS: u32 = 2000;
+}
+
+// "<resource>:<stall_ms>", e.g. "memory:100"
+impl FromStr for PsiTrigger {
+    type Err = String;
+    fn from_str(s: &str) -> Result<PsiTrigger, String> {
+        let (resource, stall_ms) = s.split_once(':')
+            .ok_or(format!("{s:?} is not in <resource>:<milliseconds> form"))?;
+        let resource = PsiResource::ALL.iter()
+            .find(|candidate| candidate.as_str() == resource)
+            .ok_or(format!("unknown resource {resource:?}, expecting cpu, memory or io"))?;
+        let stall_ms = stall_ms.parse()
+            .map_err(|e| format!("cannot parse {stall_ms:?} as milliseconds: {e}"))?;
+        if stall_ms == 0 || stall_ms >= PsiTrigger::WINDOW_MS {
+            return Err(format!("stall time must be between 1 and {} ms",
+                               PsiTrigger::WINDOW_MS - 1));
+        }
+        Ok(PsiTrigger { resource: *resource, stall_ms })
+    }
+}
+
+// an OOM kill noticed by the agent
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OomKill {
+    // in seconds since the epoch, as logged by the kernel
+    pub time: u64,
+    pub pid: u32,
+    pub process: String,
+    // of the victim, when the kernel tells (>= 4.19)
+    pub cgroup: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OomInfo {
+    // processes killed by the OOM killer since boot
+    pub kills: u64,
+    // only known for kills happening while the agent runs
+    pub last_kill: Option<OomKill>,
+}
+
+// usage of a mounted local filesystem, sizes in KiB
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FsUsage {
+    pub mountpoint: String,
+    // what is mounted, e.g. "/dev/xvda1"
+    pub device: String,
+    // VBD holding the device, when known
+    pub vbd: Option<u32>,
+    pub fstype: String,
+    pub size_kb: u64,
+    pub used_kb: u64,
+    // for unprivileged users, without the blocks reserved to root
+    pub available_kb: u64,
+    pub inodes_total: u64,
+    pub inodes_free: u64,
+}
+
+impl FsUsage {
+    pub fn changed_beyond(&self, other: &FsUsage, threshold: &ChangeThreshold) -> bool {
+        self.mountpoint != other.mountpoint
+            || self.device != other.device
+            || self.vbd != other.vbd
+            || self.fstype != other.fstype
+            || self.size_kb != other.size_kb
+            || self.inodes_total != other.inodes_total
+            || threshold.exceeded(other.used_kb, self.used_kb)
+            || threshold.exceeded(other.available_kb, self.available_kb)
+            || threshold.exceeded(other.inodes_free, self.inodes_free)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FsInfo {
+    pub filesystems: Vec<FsUsage>,
+}
+
+impl FsInfo {
+    pub fn changed_beyond(&self, other: &FsInfo, threshold: &ChangeThreshold) -> bool {
+        self.filesystems.len() != other.filesystems.len()
+            || self.filesystems.iter().zip(other.filesystems.iter()).any(
+                |(fs, other_fs)| fs.changed_beyond(other_fs, threshold))
+    }
+}
+
+// a mounted local filesystem, as far as health is concerned
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MountEntry {
+    pub mountpoint: String,
+    pub device: String,
+    pub fstype: String,
+    // options of the mount, e.g. "rw,noatime"
+    pub options: String,
+    // either the mount or the whole filesystem, as when the kernel
+    // stops writing after I/O errors
+    pub read_only: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[allow(dead_code)] // only the Linux collector produces events
+pub enum MountEventOp {
+    Mounted,
+    Unmounted,
+    // options or read-only state changed
+    Changed,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MountEvent {
+    pub op: MountEventOp,
+    pub mount: MountEntry,
+}
+
+// cumulated I/O statistics of a disk since boot
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockStats {
+    pub name: String,
+    pub vbd: Option<u32>,
+    pub read_ops: u64,
+    pub read_bytes: u64,
+    pub write_ops: u64,
+    pub write_bytes: u64,
+    pub in_flight: u64,
+    // time with I/O in flight, and time spent by requests in the
+    // queue, as sum over all requests
+    pub busy_ms: u64,
+    pub queue_ms: u64,
+}
+
+impl BlockStats {
+    // counters only grow, a percentage gets harder to reach over time
+    pub fn changed_beyond(&self, other: &BlockStats, threshold: &ChangeThreshold) -> bool {
+        self.name != other.name
+            || self.vbd != other.vbd
+            // any change when I/Os start or stop
+            || (self.in_flight == 0) != (other.in_flight == 0)
+            || [(self.read_ops, other.read_ops),
+                (self.read_bytes, other.read_bytes),
+                (self.write_ops, other.write_ops),
+                (self.write_bytes, other.write_bytes),
+                (self.busy_ms, other.busy_ms),
+                (self.queue_ms, other.queue_ms)]
+                .iter().any(|(value, other_value)| threshold.exceeded(*other_value, *value))
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BlockInfo {
+    pub disks: Vec<BlockStats>,
+}
+
+impl BlockInfo {
+    pub fn changed_beyond(&self, other: &BlockInfo, threshold: &ChangeThreshold) -> bool {
+        self.disks.len() != other.disks.len()
+            || self.disks.iter().zip(other.disks.iter()).any(
+                |(disk, other_disk)| disk.changed_beyond(other_disk, threshold))
+    }
+}
+
+// state of a collector, as far as the agent can tell
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CollectorHealth {
+    Ok,
+    // number of consecutive failed attempts to restart it
+    Failing(u32),
+}
+
 #[non_exhaustive]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ToolstackNetInterface {
     None,
     Vif(u32),
@@ -26,15 +440,189 @@ impl ToolstackNetInterface {
     }
 }
 
+// Operational state of a link, following RFC 2863 (as exposed
+// e.g. by Linux in IFLA_OPERSTATE)
+#[allow(dead_code)] // not all collectors can report every state
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NetOperState {
+    #[default]
+    Unknown,
+    NotPresent,
+    Down,
+    LowerLayerDown,
+    Testing,
+    Dormant,
+    Up,
+}
+
+impl NetOperState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetOperState::Unknown => "unknown",
+            NetOperState::NotPresent => "notpresent",
+            NetOperState::Down => "down",
+            NetOperState::LowerLayerDown => "lowerlayerdown",
+            NetOperState::Testing => "testing",
+            NetOperState::Dormant => "dormant",
+            NetOperState::Up => "up",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NetLinkState {
+    pub admin_up: bool,         // IFF_UP, as set by the guest admin
+    pub carrier: bool,
+    pub oper: NetOperState,
+}
+
+// interface traffic counters, since interface creation
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NetStats {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+impl NetStats {
+    // (direction, counter, value) triplets, for publishers
+    pub fn counters(&self) -> [(&'static str, &'static str, u64); 8] {
+        [("rx", "bytes", self.rx_bytes),
+         ("rx", "packets", self.rx_packets),
+         ("rx", "errors", self.rx_errors),
+         ("rx", "dropped", self.rx_dropped),
+         ("tx", "bytes", self.tx_bytes),
+         ("tx", "packets", self.tx_packets),
+         ("tx", "errors", self.tx_errors),
+         ("tx", "dropped", self.tx_dropped)]
+    }
+
+    // counters only grow, a percentage gets harder to reach over time
+    pub fn changed_beyond(&self, other: &NetStats, threshold: &ChangeThreshold) -> bool {
+        self.counters().iter().zip(other.counters().iter())
+            .any(|((_, _, value), (_, _, other_value))| threshold.exceeded(*other_value, *value))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NetRoute {
+    pub destination: IpAddr,
+    pub prefix_len: u8,
+    pub gateway: Option<IpAddr>,  // None for on-link routes
+    pub source: Option<IpAddr>,   // preferred source address
+}
+
+impl NetRoute {
+    #[allow(dead_code)]
+    pub fn is_default(&self) -> bool {
+        self.prefix_len == 0
+    }
+}
+
+impl fmt::Display for NetRoute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.destination, self.prefix_len)?;
+        if let Some(gateway) = self.gateway {
+            write!(f, " via {gateway}")?;
+        }
+        Ok(())
+    }
+}
+
+// what an interface is, as far as the guest OS tells us
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum NetInterfaceKind {
+    #[default]
+    Unknown,
+    Ethernet,
+    Loopback,
+    Bridge,
+    Bond,
+    Vlan,
+    Veth,
+    Tun,
+    Tap,
+    Wireguard,
+    #[allow(dead_code)] // not all collectors can tell
+    Macvlan,
+    Vxlan,
+    #[allow(dead_code)]
+    Other(String),              // as named by the OS
+}
+
+impl NetInterfaceKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            NetInterfaceKind::Unknown => "unknown",
+            NetInterfaceKind::Ethernet => "ethernet",
+            NetInterfaceKind::Loopback => "loopback",
+            NetInterfaceKind::Bridge => "bridge",
+            NetInterfaceKind::Bond => "bond",
+            NetInterfaceKind::Vlan => "vlan",
+            NetInterfaceKind::Veth => "veth",
+            NetInterfaceKind::Tun => "tun",
+            NetInterfaceKind::Tap => "tap",
+            NetInterfaceKind::Wireguard => "wireguard",
+            NetInterfaceKind::Macvlan => "macvlan",
+            NetInterfaceKind::Vxlan => "vxlan",
+            NetInterfaceKind::Other(name) => name,
+        }
+    }
+}
+
+// Network namespace of an interface, None for the one of the agent
+pub type NetNamespace = Option<String>;
+
+// Network namespaces to watch, besides the one of the agent
+#[derive(Clone, Debug, Default)]
+pub enum NetNamespaceSelection {
+    #[default]
+    None,
+    #[cfg_attr(not(feature = "net_netlink"), allow(dead_code))] // only netlink reads the names
+    Named(Vec<String>),
+    All,                        // all named ones, as found in /run/netns
+}
+
+// network collector settings, from the command line
+#[derive(Clone, Debug)]
+pub struct NetCollectorConfig {
+    pub netns: NetNamespaceSelection,
+    // full rescan period, for collectors that have to poll, and of
+    // named namespaces
+    pub poll_period: Duration,
+    // for collectors reporting counters on their own schedule
+    #[allow(dead_code)]
+    pub stats_period: Duration,
+    pub stats_threshold: ChangeThreshold,
+    // randomization of periods, in percent
+    pub jitter_percent: u8,
+}
+
 #[derive(Clone, Debug)]
 pub struct NetInterface {
+    pub netns: NetNamespace,
     pub index: u32,
     pub name: String,
+    pub kind: NetInterfaceKind,
+    // alternative names, as e.g. Linux "altname" properties
+    pub altnames: Vec<String>,
+    // hardware MAC, when the OS knows it may differ from the current one
+    pub perm_mac: Option<String>,
     pub toolstack_iface: ToolstackNetInterface,
+    // last link state notified, None until first known
+    pub link_state: Option<NetLinkState>,
+    // last counters notified
+    pub stats: Option<NetStats>,
 }
 
 impl NetInterface {
-    pub fn new(index: u32, name: Option<String>) -> NetInterface {
+    pub fn new(netns: NetNamespace, index: u32, name: Option<String>, mac: Option<&str>,
+               kind: NetInterfaceKind) -> NetInterface {
         let name = match name {
             Some(string) => string,
             None => {
@@ -42,13 +630,44 @@ impl NetInterface {
                 String::from("") // this is not valid, but user will now be aware
             },
         };
-        NetInterface { index,
-                       name: name.clone(),
-                       toolstack_iface: crate::vif_detect::get_toolstack_interface(&name),
+        // OS-specific detection only looks at the agent's namespace
+        let toolstack_iface = match netns {
+            None => crate::vif_detect::get_toolstack_interface(&name, &kind, mac),
+            Some(_) => crate::vif_detect_mac::get_toolstack_interface(&name, &kind, mac),
+        };
+        NetInterface { netns,
+                       index,
+                       name,
+                       kind,
+                       altnames: vec![],
+                       perm_mac: None,
+                       toolstack_iface,
+                       link_state: None,
+                       stats: None,
         }
     }
+
+    #[allow(dead_code)] // not all collectors and publishers need it
+    pub fn key(&self) -> NetInterfaceKey {
+        (self.netns.clone(), self.index)
+    }
+
+    // record new counters, telling whether they changed enough to be
+    // notified
+    pub fn update_stats(&mut self, stats: NetStats, threshold: &ChangeThreshold) -> bool {
+        if let Some(last_stats) = &self.stats {
+            if !stats.changed_beyond(last_stats, threshold) {
+                return false;
+            }
+        }
+        self.stats = Some(stats);
+        true
+    }
 }
 
+// interface indices are only unique within a network namespace
+pub type NetInterfaceKey = (NetNamespace, u32);
+
 // The cache of currently-known network interfaces.  We have to use
 // reference counting on the cached items, as we want on one hand to
 // use references to those items from NetEvent, and OTOH we want to
@@ -56,7 +675,7 @@ impl NetInterface {
 // use `&'static NetInterface` because we can do the latter, which is
 // good in the end.
 // The interface may change name after creation (hence `RefCell`).
-pub type NetInterfaceCache = HashMap<u32, Rc<RefCell<NetInterface>>>;
+pub type NetInterfaceCache = HashMap<NetInterfaceKey, Rc<RefCell<NetInterface>>>;
 
 #[derive(Debug)]
 pub enum NetEventOp {
@@ -66,6 +685,12 @@ pub enum NetEventOp {
     RmMac(String),
     AddIp(IpAddr),
     RmIp(IpAddr),
+    LinkState(NetLinkState),
+    #[allow(dead_code)] // not all collectors report routes
+    AddRoute(NetRoute),
+    #[allow(dead_code)]
+    RmRoute(NetRoute),
+    Stats(NetStats),
 }
 
 #[derive(Debug)]
@@ -73,3 +698,79 @@ pub struct NetEvent {
     pub iface: Rc<RefCell<NetInterface>>,
     pub op: NetEventOp,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threshold_from_str() {
+        assert_eq!("1024".parse(), Ok(ChangeThreshold::Absolute(1024)));
+        assert_eq!("0".parse(), Ok(ChangeThreshold::Absolute(0)));
+        assert_eq!("5%".parse(), Ok(ChangeThreshold::Percent(5.0)));
+        assert_eq!("0.5%".parse(), Ok(ChangeThreshold::Percent(0.5)));
+        assert_eq!("0%".parse(), Ok(ChangeThreshold::Percent(0.0)));
+        for invalid in ["", "%", "-1", "-1%", "1.5", "x", "x%", "5 %", "5%%", "NaN%"] {
+            assert!(invalid.parse::<ChangeThreshold>().is_err(), "{:?} accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn absolute_threshold() {
+        let threshold = ChangeThreshold::Absolute(100);
+        assert!(!threshold.exceeded(1000, 1099));
+        assert!(!threshold.exceeded(1000, 901));
+        // reaching the threshold is enough
+        assert!(threshold.exceeded(1000, 1100));
+        assert!(threshold.exceeded(1000, 900));
+        assert!(threshold.exceeded(0, 100));
+        assert!(threshold.exceeded(100, 0));
+    }
+
+    #[test]
+    fn percent_threshold() {
+        let threshold = ChangeThreshold::Percent(10.0);
+        assert!(!threshold.exceeded(1000, 1099));
+        assert!(!threshold.exceeded(1000, 901));
+        assert!(threshold.exceeded(1000, 1100));
+        assert!(threshold.exceeded(1000, 900));
+        assert!(threshold.exceeded(1000, 0));
+        // relative to the old value, anything is a change from 0
+        assert!(threshold.exceeded(0, 1));
+    }
+
+    #[test]
+    fn meminfo_threshold() {
+        let meminfo = MemInfo { total_kb: 8048276, available_kb: 5612348,
+                                hugepages_total: Some(4), hugepages_free: Some(4),
+                                hugepage_size_kb: Some(2048), ..Default::default() };
+        let threshold = ChangeThreshold::Absolute(1024);
+        let changed = MemInfo { available_kb: 5612348 - 1023, ..meminfo.clone() };
+        assert!(!changed.changed_beyond(&meminfo, &threshold));
+        let changed = MemInfo { available_kb: 5612348 - 1024, ..meminfo.clone() };
+        assert!(changed.changed_beyond(&meminfo, &threshold));
+        // page counts, not KiB
+        let changed = MemInfo { hugepages_free: Some(3), ..meminfo.clone() };
+        assert!(changed.changed_beyond(&meminfo, &threshold));
+        // KiB/s, not KiB
+        let changed = MemInfo { swap_in_kb_per_s: Some(1), ..meminfo.clone() };
+        let meminfo = MemInfo { swap_in_kb_per_s: Some(0), ..meminfo };
+        assert!(changed.changed_beyond(&meminfo, &threshold));
+        let changed = MemInfo { hugepages_total: None, ..meminfo.clone() };
+        assert!(changed.changed_beyond(&meminfo, &threshold));
+        assert!(!meminfo.changed_beyond(&meminfo, &threshold));
+    }
+
+    #[test]
+    fn zero_threshold() {
+        for threshold in [ChangeThreshold::default(), ChangeThreshold::Absolute(0),
+                          ChangeThreshold::Percent(0.0)] {
+            assert!(!threshold.exceeded(0, 0), "{:?}", threshold);
+            assert!(!threshold.exceeded(1000, 1000), "{:?}", threshold);
+            assert!(threshold.exceeded(1000, 1001), "{:?}", threshold);
+            assert!(threshold.exceeded(0, 1), "{:?}", threshold);
+        }
+        // no change is never worth a write
+        assert!(!ChangeThreshold::Percent(10.0).exceeded(0, 0));
+    }
+}
@@ -15,32 +15,93 @@ mod collector_net;
 #[cfg_attr(target_os = "freebsd", path = "collector_memory_bsd.rs")]
 mod collector_memory;
 
+#[cfg_attr(target_os = "linux", path = "collector_balloon_linux.rs")]
+#[cfg_attr(target_os = "freebsd", path = "collector_balloon_bsd.rs")]
+mod collector_balloon;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod balloon_progress;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod swap_rate;
+
+#[cfg_attr(target_os = "linux", path = "collector_pressure_linux.rs")]
+mod collector_pressure;
+
+#[cfg_attr(target_os = "linux", path = "collector_oom_linux.rs")]
+mod collector_oom;
+
+#[cfg_attr(target_os = "linux", path = "collector_fs_linux.rs")]
+#[cfg_attr(target_os = "freebsd", path = "collector_fs_bsd.rs")]
+mod collector_fs;
+
+#[cfg_attr(target_os = "linux", path = "collector_mounts_linux.rs")]
+mod collector_mounts;
+
+#[cfg_attr(target_os = "linux", path = "collector_block_linux.rs")]
+#[cfg_attr(target_os = "freebsd", path = "collector_block_bsd.rs")]
+mod collector_block;
+
+#[cfg_attr(target_os = "linux", path = "collector_dns_linux.rs")]
+mod collector_dns;
+
+#[cfg_attr(target_os = "linux", path = "collector_hostname_linux.rs")]
+mod collector_hostname;
+
 #[cfg_attr(target_os = "linux", path = "vif_detect_linux.rs")]
 #[cfg_attr(target_os = "freebsd", path = "vif_detect_freebsd.rs")]
 mod vif_detect;
+mod vif_detect_mac;
+#[cfg_attr(not(target_os = "freebsd"), allow(dead_code))] // only tested elsewhere
+mod vif_detect_newbus;
+
+#[cfg_attr(target_os = "linux", path = "vbd_detect_linux.rs")]
+#[cfg_attr(target_os = "freebsd", path = "vbd_detect_freebsd.rs")]
+mod vbd_detect;
 
 #[cfg_attr(target_os = "linux", path = "hypervisor_linux.rs")]
 mod hypervisor;
 
+mod net_coalesce;
+mod periodic;
+
 mod error;
 
 use clap::Parser;
 
+use crate::collector_balloon::BalloonSource;
+use crate::collector_block::BlockStatsSource;
+use crate::collector_dns::DnsSource;
+use crate::collector_fs::FilesystemSource;
+use crate::collector_hostname::HostnameSource;
 use crate::collector_memory::MemorySource;
+use crate::collector_mounts::MountSource;
 use crate::collector_net::NetworkSource;
-use crate::datastructs::KernelInfo;
+use crate::collector_oom::OomSource;
+use crate::collector_pressure::PressureSource;
+use crate::datastructs::{ChangeThreshold, CollectorHealth, KernelInfo, NetCollectorConfig,
+                         NetEvent, NetNamespaceSelection, PsiTrigger};
 use crate::hypervisor::check_is_in_xen_guest;
+use crate::net_coalesce::NetEventCoalescer;
+use crate::periodic::JitteredInterval;
 use crate::publisher::Publisher;
 
-use futures::{pin_mut, select, FutureExt, TryStreamExt};
+use futures::future::{Fuse, FusedFuture};
+use futures::{pin_mut, select, FutureExt, StreamExt, TryStreamExt};
 use std::error::Error;
 use std::io;
 use std::str::FromStr;
 use std::time::Duration;
 
 const REPORT_INTERNAL_NICS: bool = false; // FIXME make this a CLI flag
-const MEM_PERIOD_SECONDS: u64 = 60;
+const DEFAULT_MEM_PERIOD_SECONDS: u64 = 60;
+// often enough to notice a stuck balloon quickly
+const BALLOON_PERIOD_SECONDS: u64 = 10;
+const NET_RECONNECT_MAX_DELAY_SECONDS: u64 = 60;
 const DEFAULT_LOGLEVEL: &str = "info";
+const DEFAULT_NET_COALESCE_MS: u64 = 500;
+const DEFAULT_NET_POLL_PERIOD_SECONDS: u64 = 60;
+const DEFAULT_NET_STATS_PERIOD_SECONDS: u64 = 60;
+const DEFAULT_FS_PERIOD_SECONDS: u64 = 60;
+const DEFAULT_BLOCK_STATS_PERIOD_SECONDS: u64 = 60;
 
 
 #[tokio::main]
@@ -74,50 +135,396 @@ async fn main() -> Result<(), Box<dyn Error>> {
     };
     publisher.publish_static(&os_info::get(), &kernel_info, mem_total_kb)?;
 
+    // ballooning is only advertised when we have a driver
+    let mut collector_balloon = match BalloonSource::new() {
+        Ok(collector_balloon) => Some(collector_balloon),
+        Err(error) if matches!(error.kind(), io::ErrorKind::Unsupported
+                                             | io::ErrorKind::NotFound)
+            => { log::info!("no balloon driver: {error}");
+                 None
+            },
+        Err(error) => Err(error)?,
+    };
+    let mut last_balloon = None;
+    if let Some(collector_balloon) = &mut collector_balloon {
+        match collector_balloon.collect_current() {
+            Ok(balloon) => {
+                publisher.publish_balloon(&balloon)?;
+                last_balloon = Some(balloon);
+            },
+            Err(e) => log::warn!("cannot read balloon state: {e}"),
+        }
+    }
+
+    // hostname
+    let mut collector_hostname = HostnameSource::new()?;
+    match collector_hostname.collect_current().await {
+        Ok(hostname_info) => publisher.publish_hostname(&hostname_info)?,
+        Err(error) if error.kind() == io::ErrorKind::Unsupported
+            => log::warn!("hostname not supported"),
+        Err(error) => Err(error)?,
+    }
+    let hostname_stream = collector_hostname.stream();
+    pin_mut!(hostname_stream);
+
+    // resolver config
+    let mut collector_dns = DnsSource::new()?;
+    match collector_dns.collect_current() {
+        Ok(dns_info) => publisher.publish_dns(&dns_info)?,
+        Err(error) if error.kind() == io::ErrorKind::Unsupported
+            => log::warn!("DNS config not supported"),
+        Err(error) => Err(error)?,
+    }
+    let dns_stream = collector_dns.stream();
+    pin_mut!(dns_stream);
+
+    // pressure stall information, sampled with memory stats, and
+    // published right away when a trigger fires
+    let collector_pressure = match PressureSource::new(&cli.psi_trigger) {
+        Ok(collector_pressure) => Some(collector_pressure),
+        Err(error) if matches!(error.kind(), io::ErrorKind::Unsupported
+                                             | io::ErrorKind::NotFound)
+            => { log::info!("pressure stall information not available: {error}");
+                 None
+            },
+        Err(error) => Err(error)?,
+    };
+    // replaced by a dummy when the triggers fail
+    let mut pressure_stream = match &collector_pressure {
+        Some(collector_pressure) => collector_pressure.stream().boxed_local(),
+        None => futures::stream::pending().boxed_local(),
+    };
+
+    // OOM kills, checked with memory stats, and right away when the
+    // kernel reports a victim
+    let collector_oom = match OomSource::new() {
+        Ok(collector_oom) => Some(collector_oom),
+        Err(error) if matches!(error.kind(), io::ErrorKind::Unsupported
+                                             | io::ErrorKind::NotFound)
+            => { log::info!("OOM kills not available: {error}");
+                 None
+            },
+        Err(error) => Err(error)?,
+    };
+    // replaced by a dummy when it fails
+    let mut oom_stream = match &collector_oom {
+        Some(collector_oom) => collector_oom.stream().boxed_local(),
+        None => futures::stream::pending().boxed_local(),
+    };
+
     // periodic memory stat
-    let mut timer_stream = tokio::time::interval(Duration::from_secs(MEM_PERIOD_SECONDS));
+    let mut timer_stream = JitteredInterval::new(Duration::from_secs(cli.mem_period),
+                                                 cli.period_jitter);
+    // last values published, to skip insignificant changes
+    let mut last_meminfo = None;
+    let mut last_pressure = None;
+    let mut last_oom = None;
+
+    // balloon state, on its own period to follow its progress
+    let mut balloon_timer = JitteredInterval::new(Duration::from_secs(BALLOON_PERIOD_SECONDS),
+                                                  cli.period_jitter);
+    // just published
+    balloon_timer.reset();
+
+    // periodic filesystem usage
+    let mut collector_fs = FilesystemSource::new()?;
+    let mut fs_timer = JitteredInterval::new(Duration::from_secs(cli.fs_period),
+                                             cli.period_jitter);
+    let mut last_fs_info = None;
+
+    // mount changes, published right away
+    let mut collector_mounts = MountSource::new(Duration::from_secs(cli.fs_period),
+                                                 cli.period_jitter)?;
+    match collector_mounts.collect_current() {
+        Ok(mount_events) => {
+            for event in &mount_events {
+                publisher.publish_mount_event(event)?;
+            }
+        },
+        Err(error) if error.kind() == io::ErrorKind::Unsupported
+            => log::warn!("mount events not supported"),
+        Err(error) => Err(error)?,
+    }
+    let mounts_stream = collector_mounts.stream();
+    pin_mut!(mounts_stream);
+
+    // periodic block I/O statistics
+    let mut collector_block = match BlockStatsSource::new() {
+        Ok(collector_block) => Some(collector_block),
+        Err(error) if matches!(error.kind(), io::ErrorKind::Unsupported
+                                             | io::ErrorKind::NotFound)
+            => { log::info!("block I/O statistics not available: {error}");
+                 None
+            },
+        Err(error) => Err(error)?,
+    };
+    let mut block_timer = JitteredInterval::new(Duration::from_secs(cli.block_stats_period),
+                                                cli.period_jitter);
+    let mut last_block_info = None;
 
     // network events
     let network_cache = Box::leak(Box::default());
-    let mut collector_net = NetworkSource::new(network_cache)?;
+    let netns = if cli.all_netns {
+        NetNamespaceSelection::All
+    } else if !cli.netns.is_empty() {
+        NetNamespaceSelection::Named(cli.netns.clone())
+    } else {
+        NetNamespaceSelection::None
+    };
+    let net_config = NetCollectorConfig {
+        netns,
+        poll_period: Duration::from_secs(cli.net_poll_period),
+        stats_period: Duration::from_secs(cli.net_stats_period),
+        stats_threshold: cli.net_stats_threshold,
+        jitter_percent: cli.period_jitter,
+    };
+    let mut collector_net = NetworkSource::new(network_cache, &net_config)?;
+    let mut net_pending = NetEventCoalescer::new();
+    // through the coalescer nevertheless, so it knows what got published
     for event in collector_net.collect_current().await? {
-        if REPORT_INTERNAL_NICS || ! event.iface.borrow().toolstack_iface.is_none() {
-            publisher.publish_netevent(&event)?;
-        }
+        net_pending.push(event);
+    }
+    for event in net_pending.take() {
+        publish_netevent(&mut publisher, &event)?;
     }
-    let netevent_stream = collector_net.stream();
-    pin_mut!(netevent_stream); // needed for iteration
+    publisher.publish_health("network", CollectorHealth::Ok)?;
+    // boxed, so it can be replaced by a dummy while we reconnect
+    let mut netevent_stream = collector_net.stream().boxed_local().fuse();
+    let net_reconnect_timer = Fuse::terminated();
+    pin_mut!(net_reconnect_timer);
+    let mut net_failures: u32 = 0;
+    // no event since the last (re)connection
+    let mut net_quiet = true;
+    // changes are held for a while, to publish bursts as a single batch
+    let net_coalesce_window = Duration::from_millis(cli.net_coalesce_ms);
+    let net_flush_timer = Fuse::terminated();
+    pin_mut!(net_flush_timer);
 
     // main loop
     loop {
+        let mut net_reconnect = false;
         select! {
-            event = netevent_stream.try_next().fuse() => {
-                match event? {
-                    Some(event) => {
-                        if REPORT_INTERNAL_NICS || ! event.iface.borrow().toolstack_iface.is_none() {
-                            publisher.publish_netevent(&event)?;
-                        } else {
-                            log::debug!("no toolstack iface in {event:?}");
-                        }
+            event = netevent_stream.try_next() => {
+                let failure = match event {
+                    Ok(Some(event)) => {
+                        // working again after a reconnection
+                        net_failures = 0;
+                        net_quiet = false;
+                        net_pending.push(event);
+                        None
                     },
-                    // FIXME can't we handle those in `select!` directly?
-                    None => { /* closed? */ },
+                    Ok(None) if !net_quiet => {
+                        log::warn!("network event stream closed, reconnecting");
+                        net_reconnect = true;
+                        None
+                    },
+                    // closed before anything came through since reconnecting
+                    Ok(None) => Some("event stream closed".to_string()),
+                    // e.g. a failed resync dump, the connection is
+                    // not worth keeping
+                    Err(e) => Some(e.to_string()),
                 };
+                if let Some(reason) = failure {
+                    net_failures += 1;
+                    publisher.publish_health("network", CollectorHealth::Failing(net_failures))?;
+                    if net_failures == 1 {
+                        log::error!("network collector failed, reconnecting: {reason}");
+                        net_reconnect = true;
+                    } else {
+                        // failing again before anything came through
+                        let delay = net_reconnect_delay(net_failures);
+                        log::error!("network collector failed (failure {net_failures}), \
+                                     reconnecting in {delay}s: {reason}");
+                        netevent_stream = futures::stream::pending().boxed_local().fuse();
+                        net_reconnect_timer.set(tokio::time::sleep(Duration::from_secs(delay)).fuse());
+                    }
+                }
+            },
+            _ = net_reconnect_timer => net_reconnect = true,
+            _ = net_flush_timer => {
+                for event in net_pending.take() {
+                    publish_netevent(&mut publisher, &event)?;
+                }
+            },
+            hostname_info = hostname_stream.try_next().fuse() => {
+                if let Some(hostname_info) = hostname_info? {
+                    publisher.publish_hostname(&hostname_info)?;
+                }
+            },
+            dns_info = dns_stream.try_next().fuse() => {
+                if let Some(dns_info) = dns_info? {
+                    publisher.publish_dns(&dns_info)?;
+                }
             },
             _ = timer_stream.tick().fuse() => {
-                match collector_memory.get_available_kb() {
-                    Ok(mem_avail_kb) => publisher.publish_memfree(mem_avail_kb)?,
+                match collector_memory.collect_meminfo() {
+                    Ok(meminfo) => {
+                        if last_meminfo.as_ref().map_or(
+                            true, |last| meminfo.changed_beyond(last, &cli.mem_threshold)) {
+                            publisher.publish_meminfo(&meminfo)?;
+                            last_meminfo = Some(meminfo);
+                        }
+                    },
                     Err(ref e) if e.kind() == io::ErrorKind::Unsupported => (),
-                    Err(e) => Err(e)?,
+                    Err(e) => log::warn!("cannot read memory statistics, skipping sample: {e}"),
+                }
+                if let Some(collector_oom) = &collector_oom {
+                    match collector_oom.collect_current() {
+                        Ok(oom) => {
+                            if last_oom.as_ref() != Some(&oom) {
+                                publisher.publish_oom(&oom)?;
+                                last_oom = Some(oom);
+                            }
+                        },
+                        Err(e) => log::warn!("cannot read OOM kills, skipping sample: {e}"),
+                    }
+                }
+                if let Some(collector_pressure) = &collector_pressure {
+                    match collector_pressure.collect_current() {
+                        Ok(pressure) => {
+                            if last_pressure.as_ref().map_or(
+                                true, |last| !pressure.same_averages(last)) {
+                                publisher.publish_pressure(&pressure)?;
+                                last_pressure = Some(pressure);
+                            }
+                        },
+                        Err(e) => log::warn!("cannot read pressure stall information, \
+                                              skipping sample: {e}"),
+                    }
+                }
+            },
+            _ = balloon_timer.tick().fuse() => {
+                if let Some(collector_balloon) = &mut collector_balloon {
+                    match collector_balloon.collect_current() {
+                        Ok(balloon) => {
+                            if last_balloon.as_ref().map_or(
+                                true, |last| balloon.changed_beyond(last, &cli.mem_threshold)) {
+                                publisher.publish_balloon(&balloon)?;
+                                last_balloon = Some(balloon);
+                            }
+                        },
+                        // e.g. a transient sysfs read error, try next time
+                        Err(e) => log::warn!("cannot read balloon state, skipping sample: {e}"),
+                    }
+                }
+            },
+            _ = fs_timer.tick().fuse() => {
+                match collector_fs.collect_current().await {
+                    Ok(fs_info) => {
+                        if last_fs_info.as_ref().map_or(
+                            true, |last| fs_info.changed_beyond(last, &cli.fs_threshold)) {
+                            publisher.publish_fs(&fs_info)?;
+                            last_fs_info = Some(fs_info);
+                        }
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::Unsupported => (),
+                    Err(e) => log::warn!("cannot read filesystem usage, skipping sample: {e}"),
+                }
+            },
+            mount_events = mounts_stream.next().fuse() => {
+                if let Some(mount_events) = mount_events {
+                    for event in &mount_events {
+                        publisher.publish_mount_event(event)?;
+                    }
+                    // usage of new filesystems, without waiting
+                    fs_timer.trigger();
+                }
+            },
+            _ = block_timer.tick().fuse() => {
+                if let Some(collector_block) = &mut collector_block {
+                    match collector_block.collect_current() {
+                        Ok(block_info) => {
+                            if last_block_info.as_ref().map_or(
+                                true, |last| block_info.changed_beyond(last, &cli.block_stats_threshold)) {
+                                publisher.publish_block(&block_info)?;
+                                last_block_info = Some(block_info);
+                            }
+                        },
+                        Err(e) => log::warn!("cannot read block device statistics, skipping sample: {e}"),
+                    }
+                }
+            },
+            oom = oom_stream.try_next().fuse() => {
+                match oom {
+                    Ok(Some(oom)) => {
+                        if last_oom.as_ref() != Some(&oom) {
+                            publisher.publish_oom(&oom)?;
+                            last_oom = Some(oom);
+                        }
+                    },
+                    Ok(None) => (),
+                    Err(e) => {
+                        log::warn!("cannot follow OOM reports, only sampling periodically now: {e}");
+                        oom_stream = futures::stream::pending().boxed_local();
+                    },
+                }
+            },
+            pressure_info = pressure_stream.try_next().fuse() => {
+                match pressure_info {
+                    Ok(Some(pressure_info)) => {
+                        log::debug!("pressure trigger fired");
+                        publisher.publish_pressure(&pressure_info)?;
+                        last_pressure = Some(pressure_info);
+                    },
+                    Ok(None) => (),
+                    Err(e) => {
+                        log::warn!("pressure triggers failed, only sampling periodically now: {e}");
+                        pressure_stream = futures::stream::pending().boxed_local();
+                    },
                 }
             },
             complete => break,
         }
+
+        if net_reconnect {
+            // release the collector so we can reconnect it
+            netevent_stream = futures::stream::pending().boxed_local().fuse();
+            match collector_net.reconnect().await {
+                Ok(events) => {
+                    // failures get reset once events come through again
+                    if net_failures > 0 {
+                        log::info!("network collector reconnected after {net_failures} failures");
+                    }
+                    publisher.publish_health("network", CollectorHealth::Ok)?;
+                    net_quiet = true;
+                    for event in events {
+                        net_pending.push(event);
+                    }
+                    netevent_stream = collector_net.stream().boxed_local().fuse();
+                },
+                Err(e) => {
+                    net_failures += 1;
+                    let delay = net_reconnect_delay(net_failures);
+                    log::error!("cannot reconnect network collector (failure {net_failures}), \
+                                 retrying in {delay}s: {e}");
+                    publisher.publish_health("network", CollectorHealth::Failing(net_failures))?;
+                    net_reconnect_timer.set(tokio::time::sleep(Duration::from_secs(delay)).fuse());
+                },
+            }
+        }
+
+        if !net_pending.is_empty() && net_flush_timer.is_terminated() {
+            net_flush_timer.set(tokio::time::sleep(net_coalesce_window).fuse());
+        }
     }
 
     Ok(())
 }
 
+// exponential backoff, in seconds
+fn net_reconnect_delay(failures: u32) -> u64 {
+    NET_RECONNECT_MAX_DELAY_SECONDS.min(1 << failures.min(16))
+}
+
+fn publish_netevent(publisher: &mut Publisher, event: &NetEvent) -> io::Result<()> {
+    if REPORT_INTERNAL_NICS || ! event.iface.borrow().toolstack_iface.is_none() {
+        publisher.publish_netevent(event)
+    } else {
+        log::debug!("no toolstack iface in {event:?}");
+        Ok(())
+    }
+}
+
 #[derive(clap::Parser)]
 struct Cli {
     /// Print logs to stderr instead of system logs
@@ -127,6 +534,84 @@ struct Cli {
     /// Highest level of detail to log
     #[arg(short, long, default_value_t = String::from(DEFAULT_LOGLEVEL))]
     loglevel: String,
+
+    /// Randomly stretch or shrink periods by up to this percentage, so
+    /// VMs started together do not all publish at the same time
+    #[arg(long, default_value_t = 0, value_name = "PERCENT",
+          value_parser = clap::value_parser!(u8).range(0..=50))]
+    period_jitter: u8,
+
+    /// Period in seconds of memory statistics
+    #[arg(long, default_value_t = DEFAULT_MEM_PERIOD_SECONDS,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    mem_period: u64,
+
+    /// Minimum change of a memory figure, in KiB or as "<N>%", for
+    /// memory statistics to be published again (hugepage counts and
+    /// swap rates are published on any change)
+    #[arg(long, default_value = "0", value_name = "THRESHOLD")]
+    mem_threshold: ChangeThreshold,
+
+    /// Period in seconds of filesystem usage
+    #[arg(long, default_value_t = DEFAULT_FS_PERIOD_SECONDS,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    fs_period: u64,
+
+    /// Minimum change of a filesystem figure, in KiB or as "<N>%", for
+    /// its usage to be published again
+    #[arg(long, default_value = "0", value_name = "THRESHOLD")]
+    fs_threshold: ChangeThreshold,
+
+    /// Period in seconds of block device I/O statistics
+    #[arg(long, default_value_t = DEFAULT_BLOCK_STATS_PERIOD_SECONDS,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    block_stats_period: u64,
+
+    /// Minimum change of a block device counter, absolute or as "<N>%"
+    /// of its value since boot, for its statistics to be published again
+    #[arg(long, default_value = "0", value_name = "THRESHOLD")]
+    block_stats_threshold: ChangeThreshold,
+
+    /// Time window in milliseconds during which network changes are
+    /// accumulated, so changes cancelling each other are not published
+    #[arg(long, default_value_t = DEFAULT_NET_COALESCE_MS)]
+    net_coalesce_ms: u64,
+
+    /// Period in seconds of full network rescans, for collectors that
+    /// cannot be notified of every change (pnet collector), and of
+    /// named network namespaces (netlink collector)
+    #[arg(long, default_value_t = DEFAULT_NET_POLL_PERIOD_SECONDS,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    net_poll_period: u64,
+
+    /// Period in seconds of network traffic counters (netlink collector
+    /// only, the pnet collector reports them with each rescan)
+    #[arg(long, default_value_t = DEFAULT_NET_STATS_PERIOD_SECONDS,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    net_stats_period: u64,
+
+    /// Minimum change of a traffic counter, absolute or as "<N>%" of
+    /// its value since boot, for counters of an interface to be
+    /// published again
+    #[arg(long, default_value = "0", value_name = "THRESHOLD")]
+    net_stats_threshold: ChangeThreshold,
+
+    /// Named network namespace to watch, besides the agent's own,
+    /// once it exists (can be repeated, netlink collector only)
+    #[arg(long, value_name = "NAME")]
+    netns: Vec<String>,
+
+    /// Watch all named network namespaces, as found in /run/netns
+    /// every --net-poll-period (netlink collector only)
+    #[arg(long, conflicts_with = "netns")]
+    all_netns: bool,
+
+    /// Publish pressure stall information right away when tasks stall
+    /// on a resource (cpu, memory or io) for more than the given
+    /// milliseconds within 2 seconds, e.g. "memory:100" (can be
+    /// repeated, Linux only)
+    #[arg(long, value_name = "RESOURCE:MS")]
+    psi_trigger: Vec<PsiTrigger>,
 }
 
 fn setup_logger(use_stderr:bool, loglevel_string: &str) -> Result<(), Box<dyn Error>> {
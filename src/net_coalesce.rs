@@ -0,0 +1,268 @@
+use crate::datastructs::{NetEvent, NetEventOp, NetInterface, NetInterfaceKind,
+                         ToolstackNetInterface};
+use std::cell::RefCell;
+use std::mem;
+use std::rc::{Rc, Weak};
+
+// Network events waiting to be published.  Bursts of changes (DHCP
+// renewals, IPv6 privacy addresses rotation, containers coming and
+// going) get reduced to their net effect before reaching Xenstore.
+// Interfaces we never published, and their items, can vanish without
+// a trace.
+#[derive(Default)]
+pub struct NetEventCoalescer {
+    pending: Vec<NetEvent>,
+    // events dropped since last take(), for logging
+    dropped: usize,
+    // interfaces already published, with what AddIface published
+    // about them: collectors repeat AddIface on every link change
+    announced: Vec<(Weak<RefCell<NetInterface>>, IfaceAttrs)>,
+}
+
+// the interface properties published on AddIface
+#[derive(PartialEq)]
+struct IfaceAttrs {
+    name: String,
+    kind: NetInterfaceKind,
+    altnames: Vec<String>,
+    perm_mac: Option<String>,
+    toolstack_iface: ToolstackNetInterface,
+}
+
+impl IfaceAttrs {
+    fn of(iface: &NetInterface) -> IfaceAttrs {
+        IfaceAttrs {
+            name: iface.name.clone(),
+            kind: iface.kind.clone(),
+            altnames: iface.altnames.clone(),
+            perm_mac: iface.perm_mac.clone(),
+            toolstack_iface: iface.toolstack_iface.clone(),
+        }
+    }
+}
+
+impl NetEventCoalescer {
+    pub fn new() -> NetEventCoalescer {
+        NetEventCoalescer::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn push(&mut self, event: NetEvent) {
+        let same_iface = |other: &NetEvent| Rc::ptr_eq(&other.iface, &event.iface);
+        match &event.op {
+            NetEventOp::AddIface => {
+                // the publisher reads the interface when flushing,
+                // one pending addition covers all changes
+                if self.pending.iter().any(|other| same_iface(other)
+                                           && matches!(other.op, NetEventOp::AddIface)) {
+                    self.dropped += 1;
+                    return;
+                }
+            },
+            NetEventOp::RmIface => {
+                if !self.is_announced(&event) &&
+                    self.pending.iter().any(|other| same_iface(other)
+                                            && matches!(other.op, NetEventOp::AddIface)) {
+                    // appeared and vanished meanwhile, nobody needs to know
+                    let before = self.pending.len();
+                    self.pending.retain(|other| !same_iface(other));
+                    self.dropped += before - self.pending.len() + 1;
+                    return;
+                }
+            },
+            // only the latest value matters
+            NetEventOp::LinkState(_) | NetEventOp::Stats(_) => {
+                let before = self.pending.len();
+                self.pending.retain(|other| !(same_iface(other) &&
+                                              mem::discriminant(&other.op)
+                                              == mem::discriminant(&event.op)));
+                self.dropped += before - self.pending.len();
+            },
+            // collectors may repeat additions, so an addition followed
+            // by a removal does not mean the item was never published:
+            // the last operation on an item wins
+            op => {
+                if let Some(pos) = self.pending.iter().position(
+                    |other| same_iface(other) && same_item(&other.op, op)) {
+                    let previous = self.pending.remove(pos);
+                    log::trace!("{:?} supersedes {:?}", event, previous);
+                    self.dropped += 1;
+                    // unless the whole interface was never published
+                    if is_removal(op) && !is_removal(&previous.op) && !self.is_announced(&event) {
+                        self.dropped += 1;
+                        return;
+                    }
+                }
+            },
+        }
+        self.pending.push(event);
+    }
+
+    pub fn take(&mut self) -> Vec<NetEvent> {
+        if self.dropped > 0 {
+            log::debug!("coalesced network events: {} to publish, {} dropped",
+                        self.pending.len(), self.dropped);
+            self.dropped = 0;
+        }
+        let mut events = mem::take(&mut self.pending);
+        self.announced.retain(|(iface, _)| iface.strong_count() > 0);
+        events.retain(|event| {
+            match event.op {
+                NetEventOp::AddIface => {
+                    let attrs = IfaceAttrs::of(&event.iface.borrow());
+                    match self.announced.iter_mut()
+                        .find(|(iface, _)| iface.as_ptr() == Rc::as_ptr(&event.iface)) {
+                        // nothing new to publish
+                        Some((_, announced_attrs)) if *announced_attrs == attrs => return false,
+                        Some((_, announced_attrs)) => *announced_attrs = attrs,
+                        None => self.announced.push((Rc::downgrade(&event.iface), attrs)),
+                    }
+                },
+                NetEventOp::RmIface =>
+                    self.announced.retain(|(iface, _)| iface.as_ptr() != Rc::as_ptr(&event.iface)),
+                _ => (),
+            }
+            true
+        });
+        events
+    }
+
+    fn is_announced(&self, event: &NetEvent) -> bool {
+        self.announced.iter().any(|(iface, _)| iface.as_ptr() == Rc::as_ptr(&event.iface))
+    }
+}
+
+// whether both operations are about the same MAC, address or route
+fn same_item(op1: &NetEventOp, op2: &NetEventOp) -> bool {
+    match (op1, op2) {
+        (NetEventOp::AddMac(mac1) | NetEventOp::RmMac(mac1),
+         NetEventOp::AddMac(mac2) | NetEventOp::RmMac(mac2)) => mac1 == mac2,
+        (NetEventOp::AddIp(ip1) | NetEventOp::RmIp(ip1),
+         NetEventOp::AddIp(ip2) | NetEventOp::RmIp(ip2)) => ip1 == ip2,
+        (NetEventOp::AddRoute(route1) | NetEventOp::RmRoute(route1),
+         NetEventOp::AddRoute(route2) | NetEventOp::RmRoute(route2)) => route1 == route2,
+        _ => false,
+    }
+}
+
+fn is_removal(op: &NetEventOp) -> bool {
+    matches!(op, NetEventOp::RmMac(_) | NetEventOp::RmIp(_) | NetEventOp::RmRoute(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastructs::{NetLinkState, NetOperState, NetStats};
+    use std::net::IpAddr;
+
+    fn iface(index: u32) -> Rc<RefCell<NetInterface>> {
+        Rc::new(RefCell::new(NetInterface {
+            netns: None,
+            index,
+            name: format!("eth{index}"),
+            kind: NetInterfaceKind::Ethernet,
+            altnames: vec![],
+            perm_mac: None,
+            toolstack_iface: ToolstackNetInterface::Vif(index),
+            link_state: None,
+            stats: None,
+        }))
+    }
+
+    fn event(iface: &Rc<RefCell<NetInterface>>, op: NetEventOp) -> NetEvent {
+        NetEvent { iface: iface.clone(), op }
+    }
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn ops(events: &[NetEvent]) -> Vec<String> {
+        events.iter().map(|event| format!("{}:{:?}", event.iface.borrow().index, event.op))
+            .collect()
+    }
+
+    #[test]
+    fn unannounced_iface_vanishes() {
+        let mut coalescer = NetEventCoalescer::new();
+        let eth0 = iface(0);
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        coalescer.push(event(&eth0, NetEventOp::AddIp(ip("10.0.0.1"))));
+        coalescer.push(event(&eth0, NetEventOp::RmIp(ip("10.0.0.1"))));
+        coalescer.push(event(&eth0, NetEventOp::RmIface));
+        assert!(coalescer.is_empty());
+        assert!(coalescer.take().is_empty());
+    }
+
+    #[test]
+    fn unannounced_address_vanishes() {
+        let mut coalescer = NetEventCoalescer::new();
+        let eth0 = iface(0);
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        coalescer.push(event(&eth0, NetEventOp::AddIp(ip("10.0.0.1"))));
+        coalescer.push(event(&eth0, NetEventOp::RmIp(ip("10.0.0.1"))));
+        assert_eq!(ops(&coalescer.take()), ["0:AddIface"]);
+    }
+
+    #[test]
+    fn removal_after_publish_is_kept() {
+        let mut coalescer = NetEventCoalescer::new();
+        let eth0 = iface(0);
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        coalescer.push(event(&eth0, NetEventOp::AddIp(ip("10.0.0.1"))));
+        coalescer.take();
+
+        // a repeated addition does not hide the published one
+        coalescer.push(event(&eth0, NetEventOp::AddIp(ip("10.0.0.1"))));
+        coalescer.push(event(&eth0, NetEventOp::RmIp(ip("10.0.0.1"))));
+        assert_eq!(ops(&coalescer.take()), ["0:RmIp(10.0.0.1)"]);
+
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        coalescer.push(event(&eth0, NetEventOp::RmIface));
+        assert_eq!(ops(&coalescer.take()), ["0:RmIface"]);
+    }
+
+    #[test]
+    fn latest_link_state_and_stats_win() {
+        let mut coalescer = NetEventCoalescer::new();
+        let (eth0, eth1) = (iface(0), iface(1));
+        let up = NetLinkState { admin_up: true, oper: NetOperState::Up, carrier: true };
+        let down = NetLinkState { admin_up: false, oper: NetOperState::Down, carrier: false };
+        let stats = |rx_bytes| NetStats { rx_bytes, ..NetStats::default() };
+        coalescer.push(event(&eth0, NetEventOp::LinkState(down)));
+        coalescer.push(event(&eth0, NetEventOp::Stats(stats(1))));
+        coalescer.push(event(&eth1, NetEventOp::Stats(stats(10))));
+        coalescer.push(event(&eth0, NetEventOp::LinkState(up)));
+        coalescer.push(event(&eth0, NetEventOp::Stats(stats(2))));
+        let events = coalescer.take();
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().any(|event| Rc::ptr_eq(&event.iface, &eth1)
+                                  && matches!(event.op, NetEventOp::Stats(s) if s == stats(10))));
+        assert!(events.iter().any(|event| Rc::ptr_eq(&event.iface, &eth0)
+                                  && matches!(event.op, NetEventOp::LinkState(s) if s == up)));
+        assert!(events.iter().any(|event| Rc::ptr_eq(&event.iface, &eth0)
+                                  && matches!(event.op, NetEventOp::Stats(s) if s == stats(2))));
+    }
+
+    #[test]
+    fn repeated_add_iface() {
+        let mut coalescer = NetEventCoalescer::new();
+        let eth0 = iface(0);
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        assert_eq!(ops(&coalescer.take()), ["0:AddIface"]);
+
+        // nothing changed since published
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        assert!(coalescer.take().is_empty());
+
+        eth0.borrow_mut().altnames.push("enp0s3".to_string());
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        assert_eq!(ops(&coalescer.take()), ["0:AddIface"]);
+        coalescer.push(event(&eth0, NetEventOp::AddIface));
+        assert!(coalescer.take().is_empty());
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Periodic timer whose periods are randomly stretched or shrunk by up
+// to `jitter_percent`, so agents of VMs started together do not all
+// hit xenstored at the same time.  Like tokio's interval, the first
+// tick is immediate.
+pub struct JitteredInterval {
+    period: Duration,
+    jitter_percent: u8,
+    next: Instant,
+}
+
+impl JitteredInterval {
+    pub fn new(period: Duration, jitter_percent: u8) -> JitteredInterval {
+        JitteredInterval { period, jitter_percent, next: Instant::now() }
+    }
+
+    pub async fn tick(&mut self) {
+        tokio::time::sleep_until(self.next).await;
+        self.reset();
+    }
+
+    // next tick right away
+    pub fn trigger(&mut self) {
+        self.next = Instant::now();
+    }
+
+    // next tick only after a full period from now
+    pub fn reset(&mut self) {
+        self.next = Instant::now() + self.jittered_period();
+    }
+
+    fn jittered_period(&self) -> Duration {
+        let jitter = self.period.mul_f64(f64::from(self.jitter_percent) / 100.0);
+        // anywhere in [period - jitter, period + jitter]
+        self.period - jitter + jitter.mul_f64(2.0 * random_fraction())
+    }
+}
+
+// in [0, 1), good enough to spread timers without pulling a crate:
+// std seeds RandomState from the OS
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
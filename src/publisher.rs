@@ -1,5 +1,7 @@
 // default no-op Publisher implementation
-use crate::datastructs::{KernelInfo, NetEvent, NetEventOp};
+use crate::datastructs::{BalloonInfo, BlockInfo, CollectorHealth, DnsInfo, FsInfo, HostnameInfo,
+                         KernelInfo, MemInfo, MountEvent, MountEventOp, NetEvent, NetEventOp,
+                         OomInfo, PressureInfo, PsiAverages};
 use os_info;
 use std::error::Error;
 use std::io;
@@ -23,19 +25,124 @@ impl Publisher {
         }
         Ok(())
     }
-    pub fn publish_memfree(&mut self, mem_free_kb: usize) -> io::Result<()> {
-        println!("Free memory: {mem_free_kb} KB");
+    pub fn publish_meminfo(&mut self, meminfo: &MemInfo) -> io::Result<()> {
+        println!("Free memory: {} KB", meminfo.available_kb);
+        println!("Memory: {}",
+                 meminfo.fields().iter()
+                 .map(|(name, value)| format!("{name}={value}"))
+                 .collect::<Vec<String>>().join(" "));
+        for device in &meminfo.swap_devices {
+            let priority = device.priority
+                .map_or(String::new(), |priority| format!(" priority={priority}"));
+            println!("Swap {} ({}): size={} KB used={} KB{priority}", device.name, device.kind,
+                     device.size_kb, device.used_kb);
+        }
+        Ok(())
+    }
+    pub fn publish_balloon(&mut self, balloon: &BalloonInfo) -> io::Result<()> {
+        println!("Balloon: current={} KB target={} KB {}",
+                 balloon.current_kb, balloon.target_kb, balloon.state.as_str());
+        Ok(())
+    }
+    pub fn publish_pressure(&mut self, pressure: &PressureInfo) -> io::Result<()> {
+        let averages = |averages: &PsiAverages| format!("avg10={:.2} avg60={:.2} avg300={:.2}",
+                                                        averages.avg10, averages.avg60,
+                                                        averages.avg300);
+        for (resource, info) in &pressure.resources {
+            print!("Pressure {}: some {}", resource.as_str(), averages(&info.some));
+            match &info.full {
+                Some(full) => println!(" full {}", averages(full)),
+                None => println!(),
+            }
+        }
+        Ok(())
+    }
+    pub fn publish_oom(&mut self, oom: &OomInfo) -> io::Result<()> {
+        print!("OOM kills: {}", oom.kills);
+        match &oom.last_kill {
+            Some(last_kill) => println!(", last at {} on {} ({}) in cgroup {}", last_kill.time,
+                                        last_kill.pid, last_kill.process,
+                                        last_kill.cgroup.as_deref().unwrap_or("unknown")),
+            None => println!(),
+        }
+        Ok(())
+    }
+    pub fn publish_fs(&mut self, fs_info: &FsInfo) -> io::Result<()> {
+        for fs in &fs_info.filesystems {
+            let vbd = fs.vbd.map_or(String::new(), |vbd_id| format!(" (VBD {vbd_id})"));
+            println!("Filesystem {} ({} on {}{vbd}): size={} KB used={} KB available={} KB \
+                      inodes={} free inodes={}",
+                     fs.mountpoint, fs.fstype, fs.device, fs.size_kb, fs.used_kb,
+                     fs.available_kb, fs.inodes_total, fs.inodes_free);
+        }
+        Ok(())
+    }
+    pub fn publish_mount_event(&mut self, event: &MountEvent) -> io::Result<()> {
+        let mount = &event.mount;
+        match event.op {
+            MountEventOp::Unmounted => println!("Unmounted {}", mount.mountpoint),
+            MountEventOp::Mounted | MountEventOp::Changed =>
+                println!("{} {} ({} on {}): {}{}",
+                         if event.op == MountEventOp::Mounted { "Mounted" } else { "Remounted" },
+                         mount.mountpoint, mount.fstype, mount.device, mount.options,
+                         if mount.read_only { " read-only" } else { "" }),
+        }
+        Ok(())
+    }
+    pub fn publish_block(&mut self, block_info: &BlockInfo) -> io::Result<()> {
+        for disk in &block_info.disks {
+            let vbd = disk.vbd.map_or(String::new(), |vbd_id| format!(" (VBD {vbd_id})"));
+            println!("Disk {}{vbd}: read {} ops {} bytes, write {} ops {} bytes, \
+                      in flight {}, busy {} ms, queue {} ms",
+                     disk.name, disk.read_ops, disk.read_bytes, disk.write_ops,
+                     disk.write_bytes, disk.in_flight, disk.busy_ms, disk.queue_ms);
+        }
+        Ok(())
+    }
+    pub fn publish_hostname(&mut self, hostname_info: &HostnameInfo) -> io::Result<()> {
+        println!("Hostname: {}", hostname_info.hostname);
+        if let Some(fqdn) = &hostname_info.fqdn {
+            println!("FQDN: {fqdn}");
+        }
+        Ok(())
+    }
+    pub fn publish_dns(&mut self, dns_info: &DnsInfo) -> io::Result<()> {
+        println!("DNS servers: {}", dns_info.nameservers.join(" "));
+        println!("DNS search: {}", dns_info.search.join(" "));
         Ok(())
     }
     pub fn publish_netevent(&self, event: &NetEvent) -> io::Result<()> {
-        let iface_id = &event.iface.borrow().name;
+        let iface_id = match &event.iface.borrow().netns {
+            Some(netns) => format!("{}@{netns}", event.iface.borrow().name),
+            None => event.iface.borrow().name.clone(),
+        };
         match &event.op {
-            NetEventOp::AddIface => println!("{iface_id} +IFACE"),
+            NetEventOp::AddIface => {
+                let iface = event.iface.borrow();
+                println!("{iface_id} +IFACE {}", iface.kind.as_str());
+                if !iface.altnames.is_empty() {
+                    println!("{iface_id} ALTNAMES {}", iface.altnames.join(" "));
+                }
+                if let Some(perm_mac) = &iface.perm_mac {
+                    println!("{iface_id} PERM-MAC {perm_mac}");
+                }
+            },
             NetEventOp::RmIface => println!("{iface_id} -IFACE"),
             NetEventOp::AddIp(address) => println!("{iface_id} +IP  {address}"),
             NetEventOp::RmIp(address) => println!("{iface_id} -IP  {address}"),
             NetEventOp::AddMac(mac_address) => println!("{iface_id} +MAC {mac_address}"),
             NetEventOp::RmMac(mac_address) => println!("{iface_id} -MAC {mac_address}"),
+            NetEventOp::LinkState(state) =>
+                println!("{iface_id} LINK admin={} oper={} carrier={}",
+                         if state.admin_up { "up" } else { "down" },
+                         state.oper.as_str(), state.carrier),
+            NetEventOp::AddRoute(route) => println!("{iface_id} +ROUTE {route}"),
+            NetEventOp::RmRoute(route) => println!("{iface_id} -ROUTE {route}"),
+            NetEventOp::Stats(stats) =>
+                println!("{iface_id} STATS {}",
+                         stats.counters().iter()
+                         .map(|(direction, counter, value)| format!("{direction}_{counter}={value}"))
+                         .collect::<Vec<String>>().join(" ")),
         }
         Ok(())
     }
@@ -43,4 +150,13 @@ impl Publisher {
     pub fn cleanup_ifaces(&mut self) -> io::Result<()> {
         Ok(())
     }
+
+    pub fn publish_health(&mut self, collector: &str, health: CollectorHealth) -> io::Result<()> {
+        match health {
+            CollectorHealth::Ok => println!("Health {collector}: ok"),
+            CollectorHealth::Failing(failures) =>
+                println!("Health {collector}: failing ({failures} failures)"),
+        }
+        Ok(())
+    }
 }
@@ -1,4 +1,5 @@
-use crate::datastructs::{KernelInfo, NetEvent};
+use crate::datastructs::{BalloonInfo, BlockInfo, CollectorHealth, DnsInfo, FsInfo, HostnameInfo,
+                         KernelInfo, MemInfo, MountEvent, NetEvent, OomInfo, PressureInfo};
 use std::env;
 use std::error::Error;
 use std::io;
@@ -8,9 +9,18 @@ pub trait XenstoreSchema {
     fn publish_static(&mut self, os_info: &os_info::Info, kernel_info: &Option<KernelInfo>,
                       mem_total_kb: Option<usize>,
     ) -> io::Result<()>;
-    fn publish_memfree(&self, mem_free_kb: usize) -> io::Result<()>;
+    fn publish_meminfo(&mut self, meminfo: &MemInfo) -> io::Result<()>;
+    fn publish_balloon(&mut self, balloon: &BalloonInfo) -> io::Result<()>;
+    fn publish_pressure(&self, pressure: &PressureInfo) -> io::Result<()>;
+    fn publish_oom(&self, oom: &OomInfo) -> io::Result<()>;
+    fn publish_fs(&mut self, fs_info: &FsInfo) -> io::Result<()>;
+    fn publish_block(&mut self, block_info: &BlockInfo) -> io::Result<()>;
+    fn publish_mount_event(&mut self, event: &MountEvent) -> io::Result<()>;
+    fn publish_hostname(&mut self, hostname_info: &HostnameInfo) -> io::Result<()>;
+    fn publish_dns(&mut self, dns_info: &DnsInfo) -> io::Result<()>;
     fn publish_netevent(&mut self, event: &NetEvent) -> io::Result<()>;
     fn cleanup_ifaces(&mut self) -> io::Result<()>;
+    fn publish_health(&mut self, collector: &str, health: CollectorHealth) -> io::Result<()>;
 }
 
 pub struct Publisher {
@@ -31,8 +41,32 @@ impl Publisher {
     ) -> io::Result<()> {
         self.schema.publish_static(os_info, kernel_info, mem_total_kb)
     }
-    pub fn publish_memfree(&mut self, mem_free_kb: usize) -> io::Result<()> {
-        self.schema.publish_memfree(mem_free_kb)
+    pub fn publish_meminfo(&mut self, meminfo: &MemInfo) -> io::Result<()> {
+        self.schema.publish_meminfo(meminfo)
+    }
+    pub fn publish_balloon(&mut self, balloon: &BalloonInfo) -> io::Result<()> {
+        self.schema.publish_balloon(balloon)
+    }
+    pub fn publish_pressure(&mut self, pressure: &PressureInfo) -> io::Result<()> {
+        self.schema.publish_pressure(pressure)
+    }
+    pub fn publish_oom(&mut self, oom: &OomInfo) -> io::Result<()> {
+        self.schema.publish_oom(oom)
+    }
+    pub fn publish_fs(&mut self, fs_info: &FsInfo) -> io::Result<()> {
+        self.schema.publish_fs(fs_info)
+    }
+    pub fn publish_block(&mut self, block_info: &BlockInfo) -> io::Result<()> {
+        self.schema.publish_block(block_info)
+    }
+    pub fn publish_mount_event(&mut self, event: &MountEvent) -> io::Result<()> {
+        self.schema.publish_mount_event(event)
+    }
+    pub fn publish_hostname(&mut self, hostname_info: &HostnameInfo) -> io::Result<()> {
+        self.schema.publish_hostname(hostname_info)
+    }
+    pub fn publish_dns(&mut self, dns_info: &DnsInfo) -> io::Result<()> {
+        self.schema.publish_dns(dns_info)
     }
     pub fn publish_netevent(&mut self, event: &NetEvent) -> io::Result<()> {
         self.schema.publish_netevent(event)
@@ -41,6 +75,9 @@ impl Publisher {
     pub fn cleanup_ifaces(&mut self) -> io::Result<()> {
         self.schema.cleanup_ifaces()
     }
+    pub fn publish_health(&mut self, collector: &str, health: CollectorHealth) -> io::Result<()> {
+        self.schema.publish_health(collector, health)
+    }
 }
 
 fn schema_from_name(name: &str) -> io::Result<&'static dyn Fn(Xs) -> Box<dyn XenstoreSchema>> {
@@ -0,0 +1,31 @@
+use std::time::Instant;
+
+// Turns the cumulated counts of swapped pages into KiB/s rates
+pub struct SwapRate {
+    pagesize: usize,
+    // (when, pages in, pages out)
+    last: Option<(Instant, u64, u64)>,
+}
+
+impl SwapRate {
+    pub fn new(pagesize: usize) -> SwapRate {
+        SwapRate { pagesize, last: None }
+    }
+
+    // (in, out) rates since the previous sample, unknown on the first
+    pub fn update(&mut self, pages_in: u64, pages_out: u64) -> Option<(usize, usize)> {
+        let now = Instant::now();
+        let last = self.last.replace((now, pages_in, pages_out));
+        let (last_time, last_in, last_out) = last?;
+        let secs = now.duration_since(last_time).as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        // counters only go back on wraparound
+        let rate = |pages: u64, last_pages: u64| {
+            (pages.wrapping_sub(last_pages) as f64 * self.pagesize as f64 / 1024.0 / secs)
+                .round() as usize
+        };
+        Some((rate(pages_in, last_in), rate(pages_out, last_out)))
+    }
+}
@@ -0,0 +1,38 @@
+use crate::vif_detect_newbus::{parse_driver_name, vbd_id_from_devinfo};
+use sysctl::Sysctl;
+
+// identifies the VBD behind an xbd(4) disk from the Xenstore node of
+// the blkfront device, as found in newbus information.
+
+// Emulated disks (ada, nvd) are not blkfront devices and have no
+// frontend node to map from, they get no VBD id.
+
+// FIXME GEOM labels are not mapped
+
+// `device` is a disk or partition name or path, e.g. "/dev/xbd0p2"
+pub fn get_vbd_id(device: &str) -> Option<u32> {
+    let name = device.strip_prefix("/dev/").unwrap_or(device);
+    // "xbd0p2" or "xbd0s1a" -> "xbd0"
+    let disk_len = name.find(|c: char| c.is_ascii_digit())
+        .map(|unit_start| unit_start + name[unit_start..]
+             .find(|c: char| !c.is_ascii_digit())
+             .unwrap_or(name.len() - unit_start))?;
+    let (driver, unit) = parse_driver_name(&name[..disk_len])?;
+    if driver != "xbd" {
+        log::debug!("block device {name} is not a VBD");
+        return None;
+    }
+    for node in ["%pnpinfo", "%location"] {
+        let ctl_name = format!("dev.{driver}.{unit}.{node}");
+        match sysctl::Ctl::new(&ctl_name).and_then(|ctl| ctl.value_string()) {
+            Ok(devinfo) => {
+                if let Some(vbd_id) = vbd_id_from_devinfo(&devinfo) {
+                    return Some(vbd_id);
+                }
+            },
+            Err(e) => log::debug!("reading {ctl_name}: {e}"),
+        }
+    }
+    log::debug!("block device {name} is not attached to a VBD");
+    None
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::Path;
+
+// identifies the VBD behind a block device, from the sysfs
+// nodename="device/vbd/$ID" of the blkfront disk holding it.
+// Partitions are mapped to their disk, device-mapper devices (LVM,
+// LUKS) to the VBD of their underlying devices when they all agree.
+
+// Emulated disks (e.g. NVMe) are not blkfront devices and have no
+// nodename to map from, they get no VBD id.
+
+const SYS_CLASS_BLOCK: &str = "/sys/class/block";
+
+// `device` is a block device name or path, e.g. "/dev/xvda1"
+pub fn get_vbd_id(device: &str) -> Option<u32> {
+    // follow /dev/mapper/* and /dev/disk/by-*/* symlinks
+    let name = match fs::canonicalize(device) {
+        Ok(path) => path.file_name()?.to_string_lossy().into_owned(),
+        Err(_) => device.strip_prefix("/dev/").unwrap_or(device).to_string(),
+    };
+    vbd_id_from_block_name(Path::new(SYS_CLASS_BLOCK), &name, 0)
+}
+
+// `sys_class_block` is where sysfs lists block devices
+fn vbd_id_from_block_name(sys_class_block: &Path, name: &str, depth: u32) -> Option<u32> {
+    // stacked devices do not go that deep, don't loop forever
+    if depth > 8 {
+        return None;
+    }
+    let sys_path = sys_class_block.join(name);
+    let disk_path = if sys_path.join("partition").exists() {
+        // partitions live below their disk
+        fs::canonicalize(&sys_path).ok()?.parent()?.to_path_buf()
+    } else {
+        sys_path.clone()
+    };
+
+    match fs::read_to_string(disk_path.join("device/nodename")) {
+        Ok(nodename) => {
+            let nodename = nodename.trim();
+            const PREFIX: &str = "device/vbd/";
+            let Some(vbd_id) = nodename.strip_prefix(PREFIX) else {
+                log::debug!("ignoring block device {name}, {nodename:?} not under {PREFIX}");
+                return None;
+            };
+            match vbd_id.parse() {
+                Ok(vbd_id) => Some(vbd_id),
+                Err(e) => {
+                    log::error!("cannot parse a VBD id in {nodename:?}: {e}");
+                    None
+                },
+            }
+        },
+        Err(_) => {
+            let slaves = fs::read_dir(sys_path.join("slaves")).ok()?
+                .flatten()
+                .map(|slave| slave.file_name().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            if slaves.is_empty() {
+                log::debug!("block device {name} is not a VBD");
+                return None;
+            }
+            let vbd_ids = slaves.iter()
+                .map(|slave| vbd_id_from_block_name(sys_class_block, slave, depth + 1))
+                .collect::<Vec<_>>();
+            match vbd_ids[0] {
+                Some(vbd_id) if vbd_ids.iter().all(|other| *other == Some(vbd_id)) =>
+                    Some(vbd_id),
+                _ => {
+                    log::debug!("block device {name} does not sit on a single VBD: {slaves:?}");
+                    None
+                },
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    // a sysfs lookalike under the temporary directory, removed on drop
+    struct SysTree(PathBuf);
+
+    impl SysTree {
+        fn new(name: &str) -> SysTree {
+            let root = std::env::temp_dir()
+                .join(format!("xen-guest-agent-test-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("class/block")).unwrap();
+            SysTree(root)
+        }
+
+        fn class_block(&self) -> PathBuf {
+            self.0.join("class/block")
+        }
+
+        // `device` relative to devices/, listed as its last component
+        fn add_device(&self, device: &str) -> PathBuf {
+            let path = self.0.join("devices").join(device);
+            fs::create_dir_all(&path).unwrap();
+            let name = path.file_name().unwrap();
+            symlink(Path::new("../../devices").join(device), self.class_block().join(name))
+                .unwrap();
+            path
+        }
+
+        fn add_vbd(&self, name: &str, vbd_id: u32) -> PathBuf {
+            let disk = self.add_device(&format!("vbd-{vbd_id}/block/{name}"));
+            fs::create_dir(disk.join("device")).unwrap();
+            fs::write(disk.join("device/nodename"), format!("device/vbd/{vbd_id}\n")).unwrap();
+            disk
+        }
+
+        fn add_partition(&self, disk: &str, name: &str) {
+            let disk = fs::canonicalize(self.class_block().join(disk)).unwrap();
+            let devices = fs::canonicalize(self.0.join("devices")).unwrap();
+            let partition = disk.strip_prefix(devices).unwrap().join(name);
+            let path = self.add_device(partition.to_str().unwrap());
+            fs::write(path.join("partition"), "1\n").unwrap();
+        }
+
+        fn add_dm(&self, name: &str, slaves: &[&str]) {
+            let path = self.add_device(&format!("virtual/block/{name}"));
+            fs::create_dir(path.join("slaves")).unwrap();
+            // only their names are read
+            for slave in slaves {
+                symlink(Path::new("../../").join(slave), path.join("slaves").join(slave))
+                    .unwrap();
+            }
+        }
+
+        fn vbd_id(&self, name: &str) -> Option<u32> {
+            vbd_id_from_block_name(&self.class_block(), name, 0)
+        }
+    }
+
+    impl Drop for SysTree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn disks_and_partitions() {
+        let sys = SysTree::new("partitions");
+        sys.add_vbd("xvda", 51712);
+        sys.add_partition("xvda", "xvda1");
+        sys.add_partition("xvda", "xvda2");
+        sys.add_device("virtual/block/loop0");
+        assert_eq!(sys.vbd_id("xvda"), Some(51712));
+        assert_eq!(sys.vbd_id("xvda2"), Some(51712));
+        assert_eq!(sys.vbd_id("loop0"), None);
+        assert_eq!(sys.vbd_id("xvdz"), None);
+    }
+
+    #[test]
+    fn device_mapper() {
+        let sys = SysTree::new("dm");
+        sys.add_vbd("xvda", 51712);
+        sys.add_partition("xvda", "xvda2");
+        sys.add_vbd("xvdb", 51728);
+        sys.add_vbd("xvdc", 51744);
+        // LUKS on a partition, and LVM on top of it
+        sys.add_dm("dm-0", &["xvda2"]);
+        sys.add_dm("dm-1", &["dm-0"]);
+        // a volume group spanning two VBDs
+        sys.add_dm("dm-2", &["xvdb", "xvdc"]);
+        sys.add_dm("dm-3", &["xvdb", "dm-1"]);
+        assert_eq!(sys.vbd_id("dm-0"), Some(51712));
+        assert_eq!(sys.vbd_id("dm-1"), Some(51712));
+        assert_eq!(sys.vbd_id("dm-2"), None);
+        assert_eq!(sys.vbd_id("dm-3"), None);
+    }
+
+    #[test]
+    fn not_a_vbd() {
+        let sys = SysTree::new("not-vbd");
+        let disk = sys.add_device("pci0000:00/0000:00:04.0/nvme/nvme0/nvme0n1");
+        fs::create_dir(disk.join("device")).unwrap();
+        sys.add_dm("dm-0", &["nvme0n1"]);
+        assert_eq!(sys.vbd_id("nvme0n1"), None);
+        assert_eq!(sys.vbd_id("dm-0"), None);
+        // a device-mapper device looping on itself
+        sys.add_dm("dm-1", &["dm-1"]);
+        assert_eq!(sys.vbd_id("dm-1"), None);
+    }
+}
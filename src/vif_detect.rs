@@ -1,5 +1,9 @@
-use crate::datastructs::{NetEvent, ToolstackNetInterface};
+use crate::datastructs::{NetInterfaceKind, ToolstackNetInterface};
+use crate::vif_detect_mac;
 
-pub fn get_toolstack_interface(iface_name: &str) -> ToolstackNetInterface {
-    return ToolstackNetInterface::None;
+// no OS-specific way to identify VIFs, only their MAC can tell
+
+pub fn get_toolstack_interface(iface_name: &str, kind: &NetInterfaceKind, mac: Option<&str>)
+                               -> ToolstackNetInterface {
+    vif_detect_mac::get_toolstack_interface(iface_name, kind, mac)
 }
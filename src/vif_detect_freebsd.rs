@@ -1,8 +1,95 @@
-use crate::datastructs::ToolstackNetInterface;
+use crate::datastructs::{NetInterfaceKind, ToolstackNetInterface};
+use crate::vif_detect_mac;
+use crate::vif_detect_newbus::{parse_driver_name, vif_id_from_devinfo};
+use std::ffi::{CStr, CString};
+use std::io;
+use std::ptr;
+use sysctl::Sysctl;
 
-// identifies a VIF as named "xn%ID"
+// identifies a VIF from the Xenstore node of the netfront device
+// behind the interface, as found in newbus information.  If that is
+// not available, guess from a "xn%ID" name, or from its MAC if it was
+// renamed.
 
-pub fn get_toolstack_interface(iface_name: &str) -> ToolstackNetInterface {
+// net.link.generic.ifdata.<ifindex>.drivername, only reachable by OID
+const NETLINK_GENERIC: libc::c_int = 0;
+const IFMIB_IFDATA: libc::c_int = 2;
+const IFDATA_DRIVERNAME: libc::c_int = 3;
+
+pub fn get_toolstack_interface(iface_name: &str, kind: &NetInterfaceKind, mac: Option<&str>)
+                               -> ToolstackNetInterface {
+    match get_newbus_toolstack_interface(iface_name) {
+        Ok(toolstack_iface) => {
+            if let (ToolstackNetInterface::Vif(vif_id), Some(mac)) = (&toolstack_iface, mac) {
+                vif_detect_mac::check_vif_mac(*vif_id, mac);
+            }
+            return toolstack_iface;
+        },
+        Err(e) => log::debug!("cannot get device info for {iface_name}, guessing: {e}"),
+    }
+
+    let toolstack_iface = get_toolstack_interface_from_name(iface_name);
+    let Some(mac) = mac else {
+        return toolstack_iface;
+    };
+    match toolstack_iface {
+        ToolstackNetInterface::Vif(vif_id) => {
+            vif_detect_mac::check_vif_mac(vif_id, mac);
+            toolstack_iface
+        },
+        ToolstackNetInterface::None =>
+            vif_detect_mac::get_toolstack_interface(iface_name, kind, Some(mac)),
+    }
+}
+
+fn get_newbus_toolstack_interface(iface_name: &str) -> io::Result<ToolstackNetInterface> {
+    // the driver name is kept when the interface gets renamed
+    let driver_name = get_driver_name(iface_name)?;
+    let Some((driver, unit)) = parse_driver_name(&driver_name) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("cannot parse driver name {driver_name:?}")));
+    };
+    if driver != "xn" {
+        log::debug!("ignoring interface {iface_name}, driver {driver_name} is not netfront");
+        return Ok(ToolstackNetInterface::None);
+    }
+    for node in ["%pnpinfo", "%location"] {
+        let ctl_name = format!("dev.{driver}.{unit}.{node}");
+        match sysctl::Ctl::new(&ctl_name).and_then(|ctl| ctl.value_string()) {
+            Ok(devinfo) => {
+                if let Some(vif_id) = vif_id_from_devinfo(&devinfo) {
+                    return Ok(ToolstackNetInterface::Vif(vif_id));
+                }
+            },
+            Err(e) => log::debug!("reading {ctl_name}: {e}"),
+        }
+    }
+    log::debug!("ignoring interface {iface_name}, {driver_name} is not attached to a VIF");
+    Ok(ToolstackNetInterface::None)
+}
+
+fn get_driver_name(iface_name: &str) -> io::Result<String> {
+    let c_iface_name = CString::new(iface_name)?;
+    let iface_index = unsafe { libc::if_nametoindex(c_iface_name.as_ptr()) };
+    if iface_index == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mib = [libc::CTL_NET, libc::PF_LINK, NETLINK_GENERIC, IFMIB_IFDATA,
+               iface_index as libc::c_int, IFDATA_DRIVERNAME];
+    let mut buf = [0u8; 64];
+    let mut len = buf.len();
+    let ret = unsafe { libc::sysctl(mib.as_ptr(), mib.len() as libc::c_uint,
+                                    buf.as_mut_ptr() as *mut libc::c_void, &mut len,
+                                    ptr::null(), 0) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let driver_name = CStr::from_bytes_until_nul(&buf[..len])
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(driver_name.to_string_lossy().into_owned())
+}
+
+fn get_toolstack_interface_from_name(iface_name: &str) -> ToolstackNetInterface {
     const PREFIX: &str = "xn";
     if !iface_name.starts_with(PREFIX) {
         log::debug!("ignoring interface {iface_name} as not starting with '{PREFIX}'");
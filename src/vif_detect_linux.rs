@@ -1,44 +1,139 @@
-use crate::datastructs::ToolstackNetInterface;
+use crate::datastructs::{NetInterfaceKind, ToolstackNetInterface};
+use crate::vif_detect_mac;
+use std::ffi::CStr;
 use std::fs;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
 
-// identifies a VIF from sysfs as devtype="vif", and take the VIF id
-// from nodename="device/vif/$ID"
+// identifies a VIF from its ethtool driver info as driver="vif", and
+// take the VIF id from bus-info="vif-$ID"; or failing that from sysfs
+// as devtype="vif", and take the VIF id from nodename="device/vif/$ID".
+// Only when the kernel cannot tell, fall back to the MAC of Ethernet
+// interfaces: those stacked on a VIF (e.g. a bridge) share its MAC.
 
 // FIXME does not attempt to detect sr-iov VIFs
 
-pub fn get_toolstack_interface(iface_name: &str) -> ToolstackNetInterface {
-    // FIXME: using ETHTOOL ioctl could be better
+// from linux/ethtool.h
+const ETHTOOL_GDRVINFO: u32 = 0x00000003;
+#[repr(C)]
+struct EthtoolDrvinfo {
+    cmd: u32,
+    driver: [libc::c_char; 32],
+    version: [libc::c_char; 32],
+    fw_version: [libc::c_char; 32],
+    bus_info: [libc::c_char; 32],
+    erom_version: [libc::c_char; 32],
+    reserved2: [libc::c_char; 12],
+    n_priv_flags: u32,
+    n_stats: u32,
+    testinfo_len: u32,
+    eedump_len: u32,
+    regdump_len: u32,
+}
+
+pub fn get_toolstack_interface(iface_name: &str, kind: &NetInterfaceKind, mac: Option<&str>)
+                               -> ToolstackNetInterface {
+    let toolstack_iface = get_ethtool_toolstack_interface(iface_name)
+        .or_else(|e| {
+            log::debug!("cannot get ethtool driver info for {iface_name}, trying sysfs: {e}");
+            get_sysfs_toolstack_interface(iface_name)
+        });
+    match (toolstack_iface, mac) {
+        // the kernel is reliable, the MAC is only checked for consistency
+        (Ok(ToolstackNetInterface::Vif(vif_id)), Some(mac)) => {
+            vif_detect_mac::check_vif_mac(vif_id, mac);
+            ToolstackNetInterface::Vif(vif_id)
+        },
+        (Ok(toolstack_iface), _) => toolstack_iface,
+        (Err(e), mac) => {
+            log::debug!("cannot get sysfs device info for {iface_name}, trying its MAC: {e}");
+            vif_detect_mac::get_toolstack_interface(iface_name, kind, mac)
+        },
+    }
+}
+
+fn get_ethtool_toolstack_interface(iface_name: &str) -> io::Result<ToolstackNetInterface> {
+    let (driver, bus_info) = get_ethtool_drvinfo(iface_name)?;
+    if driver != "vif" {
+        log::debug!("ignoring interface {iface_name}, driver {driver:?} not 'vif'");
+        return Ok(ToolstackNetInterface::None);
+    }
+    const PREFIX: &str = "vif-";
+    let Some(vif_id) = bus_info.strip_prefix(PREFIX) else {
+        log::error!("ignoring interface {iface_name}, bus-info {bus_info:?} not starting with {PREFIX}");
+        return Ok(ToolstackNetInterface::None);
+    };
+    match vif_id.parse() {
+        Ok(vif_id) => Ok(ToolstackNetInterface::Vif(vif_id)),
+        Err(e) => {
+            log::error!("ignoring interface {iface_name}, cannot parse VIF id in {bus_info:?}: {e}");
+            Ok(ToolstackNetInterface::None)
+        },
+    }
+}
+
+// returns (driver, bus-info)
+fn get_ethtool_drvinfo(iface_name: &str) -> io::Result<(String, String)> {
+    let mut ifreq: libc::ifreq = unsafe { mem::zeroed() };
+    if iface_name.len() >= ifreq.ifr_name.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  format!("interface name {iface_name:?} too long")));
+    }
+    for (dst, src) in ifreq.ifr_name.iter_mut().zip(iface_name.bytes()) {
+        *dst = src as libc::c_char;
+    }
+    let mut drvinfo: EthtoolDrvinfo = unsafe { mem::zeroed() };
+    drvinfo.cmd = ETHTOOL_GDRVINFO;
+    ifreq.ifr_ifru.ifru_data = &mut drvinfo as *mut EthtoolDrvinfo as *mut libc::c_char;
+
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    if unsafe { libc::ioctl(socket.as_raw_fd(), libc::SIOCETHTOOL as _, &mut ifreq) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let field = |chars: &[libc::c_char]| {
+        // the kernel NUL-terminates those
+        unsafe { CStr::from_ptr(chars.as_ptr()) }.to_string_lossy().into_owned()
+    };
+    Ok((field(&drvinfo.driver), field(&drvinfo.bus_info)))
+}
+
+// fails when sysfs has no device type to tell
+fn get_sysfs_toolstack_interface(iface_name: &str) -> io::Result<ToolstackNetInterface> {
     let device_path = format!("/sys/class/net/{}/device", iface_name);
-    match fs::read_to_string(format!("{device_path}/devtype")) {
-        Ok(devtype) => {
-            let devtype = devtype.trim();
-            if devtype != "vif" {
-                log::debug!("ignoring device {device_path}, devtype {devtype:?} not 'vif'");
-                return ToolstackNetInterface::None;
+    let devtype = fs::read_to_string(format!("{device_path}/devtype"))
+        .map_err(|e| io::Error::new(e.kind(), format!("reading {device_path}/devtype: {e}")))?;
+    let devtype = devtype.trim();
+    if devtype != "vif" {
+        log::debug!("ignoring device {device_path}, devtype {devtype:?} not 'vif'");
+        return Ok(ToolstackNetInterface::None);
+    }
+    match fs::read_to_string(format!("{device_path}/nodename")) {
+        Ok(nodename) => {
+            let nodename = nodename.trim();
+            const PREFIX: &str = "device/vif/";
+            if !nodename.starts_with(PREFIX) {
+                log::debug!("ignoring interface {nodename} as not under {PREFIX}");
+                return Ok(ToolstackNetInterface::None);
             }
-            match fs::read_to_string(format!("{device_path}/nodename")) {
-                Ok(nodename) => {
-                    let nodename = nodename.trim();
-                    const PREFIX: &str = "device/vif/";
-                    if !nodename.starts_with(PREFIX) {
-                        log::debug!("ignoring interface {nodename} as not under {PREFIX}");
-                        return ToolstackNetInterface::None;
-                    }
-                    let vif_id = nodename[PREFIX.len()..].parse().unwrap();
-
-                    ToolstackNetInterface::Vif(vif_id)
-                },
+            match nodename[PREFIX.len()..].parse() {
+                Ok(vif_id) => Ok(ToolstackNetInterface::Vif(vif_id)),
                 Err(e) => {
-                    log::error!("reading {device_path}/nodename: {e}");
+                    log::error!("cannot parse a VIF id in {nodename:?}: {e}");
 
-                    ToolstackNetInterface::None
+                    Ok(ToolstackNetInterface::None)
                 },
             }
         },
         Err(e) => {
-            log::debug!("reading {device_path}/devtype: {e}");
+            log::error!("reading {device_path}/nodename: {e}");
 
-            ToolstackNetInterface::None
+            Ok(ToolstackNetInterface::None)
         },
     }
 }
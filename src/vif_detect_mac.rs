@@ -0,0 +1,215 @@
+// Portable VIF detection, matching the MAC of an interface against
+// the ones in the frontend nodes of our own Xenstore tree
+// ("device/vif/$ID/mac").
+//
+// Interfaces stacked on a VIF (bridges, bonds) may share its MAC, so
+// this is only to be relied on when the OS gives us nothing better.
+
+use crate::datastructs::{NetInterfaceKind, ToolstackNetInterface};
+use std::io;
+#[cfg(feature = "xenstore")]
+use std::cell::RefCell;
+#[cfg(feature = "xenstore")]
+use xenstore_rs::{Xs, XsOpenFlags};
+
+// the Xenstore reads we need, so they can be done on a fake tree
+#[cfg_attr(not(feature = "xenstore"), allow(dead_code))]
+trait FrontendTree {
+    fn directory(&self, path: &str) -> io::Result<Vec<String>>;
+    fn read(&self, path: &str) -> io::Result<String>;
+}
+
+#[cfg(feature = "xenstore")]
+impl FrontendTree for Xs {
+    fn directory(&self, path: &str) -> io::Result<Vec<String>> {
+        Xs::directory(self, None, path)
+    }
+    fn read(&self, path: &str) -> io::Result<String> {
+        Xs::read(self, None, path)
+    }
+}
+
+#[cfg(feature = "xenstore")]
+thread_local! {
+    // opened on first use, shared by all lookups
+    static XS: RefCell<Option<Xs>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "xenstore")]
+fn with_xenstore<T>(f: impl FnOnce(&dyn FrontendTree) -> Option<T>) -> Option<T> {
+    XS.with(|xs| {
+        let mut xs = xs.borrow_mut();
+        if xs.is_none() {
+            match Xs::new(XsOpenFlags::ReadOnly) {
+                Ok(handle) => *xs = Some(handle),
+                Err(e) => {
+                    log::debug!("cannot open xenstore to look at VIF MACs: {e}");
+                    return None;
+                },
+            }
+        }
+        f(xs.as_ref()?)
+    })
+}
+
+#[cfg(not(feature = "xenstore"))]
+fn with_xenstore<T>(_f: impl FnOnce(&dyn FrontendTree) -> Option<T>) -> Option<T> {
+    None
+}
+
+// only plain Ethernet interfaces, as those stacked on a VIF share its MAC
+pub fn get_toolstack_interface(iface_name: &str, kind: &NetInterfaceKind, mac: Option<&str>)
+                               -> ToolstackNetInterface {
+    if *kind != NetInterfaceKind::Ethernet {
+        log::debug!("not looking up VIF of {} interface {iface_name} by MAC", kind.as_str());
+        return ToolstackNetInterface::None;
+    }
+    match mac.and_then(vif_from_mac) {
+        Some(vif_id) => {
+            log::debug!("interface {iface_name} identified as VIF {vif_id} from its MAC");
+            ToolstackNetInterface::Vif(vif_id)
+        },
+        None => ToolstackNetInterface::None,
+    }
+}
+
+fn vif_from_mac(mac: &str) -> Option<u32> {
+    with_xenstore(|xs| find_vif_by_mac(xs, mac))
+}
+
+// warn when the toolstack does not agree with the guest about the MAC
+// of a VIF
+pub fn check_vif_mac(vif_id: u32, mac: &str) {
+    if let Some(vif_mac) = with_xenstore(|xs| mismatching_vif_mac(xs, vif_id, mac)) {
+        log::warn!("MAC mismatch for VIF {vif_id}: {mac} in guest, {vif_mac} in xenstore");
+    }
+}
+
+#[cfg_attr(not(feature = "xenstore"), allow(dead_code))]
+fn find_vif_by_mac(xs: &dyn FrontendTree, mac: &str) -> Option<u32> {
+    let vif_ids = match xs.directory("device/vif") {
+        Ok(vif_ids) => vif_ids,
+        Err(e) => {
+            log::debug!("cannot list device/vif: {e}");
+            return None;
+        },
+    };
+    let mut found = None;
+    for vif_id in vif_ids {
+        let Ok(vif_mac) = xs.read(&format!("device/vif/{vif_id}/mac")) else {
+            continue;
+        };
+        if !vif_mac.eq_ignore_ascii_case(mac) {
+            continue;
+        }
+        let vif_id = match vif_id.parse() {
+            Ok(vif_id) => vif_id,
+            Err(e) => {
+                log::error!("cannot parse VIF id {vif_id:?}: {e}");
+                continue;
+            },
+        };
+        if let Some(other_vif_id) = found {
+            log::warn!("VIFs {other_vif_id} and {vif_id} share MAC {mac}, cannot tell which");
+            return None;
+        }
+        found = Some(vif_id);
+    }
+    found
+}
+
+// the Xenstore MAC of the VIF, if it differs
+#[cfg_attr(not(feature = "xenstore"), allow(dead_code))]
+fn mismatching_vif_mac(xs: &dyn FrontendTree, vif_id: u32, mac: &str) -> Option<String> {
+    match xs.read(&format!("device/vif/{vif_id}/mac")) {
+        Ok(vif_mac) if !vif_mac.eq_ignore_ascii_case(mac) => Some(vif_mac),
+        Ok(_) => None,
+        Err(e) => {
+            log::debug!("cannot read MAC of VIF {vif_id}: {e}");
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // flat path -> value map, directories derived from the paths
+    struct FakeTree(BTreeMap<&'static str, &'static str>);
+
+    impl FrontendTree for FakeTree {
+        fn directory(&self, path: &str) -> io::Result<Vec<String>> {
+            let prefix = format!("{path}/");
+            let mut entries: Vec<String> = self.0.keys()
+                .filter_map(|key| key.strip_prefix(&prefix))
+                .map(|rest| rest.split('/').next().unwrap().to_string())
+                .collect();
+            entries.dedup();
+            if entries.is_empty() {
+                return Err(io::Error::from(io::ErrorKind::NotFound));
+            }
+            Ok(entries)
+        }
+        fn read(&self, path: &str) -> io::Result<String> {
+            self.0.get(path).map(|value| value.to_string())
+                .ok_or(io::Error::from(io::ErrorKind::NotFound))
+        }
+    }
+
+    fn tree(nodes: &[(&'static str, &'static str)]) -> FakeTree {
+        FakeTree(nodes.iter().copied().collect())
+    }
+
+    #[test]
+    fn mac_case() {
+        let xs = tree(&[("device/vif/0/mac", "00:16:3e:aa:bb:01"),
+                        ("device/vif/1/mac", "00:16:3E:AA:BB:02")]);
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:01"), Some(0));
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3E:AA:BB:01"), Some(0));
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:02"), Some(1));
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:03"), None);
+        assert_eq!(mismatching_vif_mac(&xs, 1, "00:16:3e:aa:bb:02"), None);
+        assert_eq!(mismatching_vif_mac(&xs, 1, "00:16:3e:aa:bb:01").as_deref(),
+                   Some("00:16:3E:AA:BB:02"));
+    }
+
+    #[test]
+    fn no_vif_nodes() {
+        let xs = tree(&[("device/vbd/768/state", "4")]);
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:01"), None);
+        assert_eq!(mismatching_vif_mac(&xs, 0, "00:16:3e:aa:bb:01"), None);
+        // a VIF node without a MAC, e.g. while being set up
+        let xs = tree(&[("device/vif/0/state", "1"),
+                        ("device/vif/1/mac", "00:16:3e:aa:bb:01")]);
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:01"), Some(1));
+        assert_eq!(mismatching_vif_mac(&xs, 0, "00:16:3e:aa:bb:01"), None);
+    }
+
+    #[test]
+    fn duplicate_macs() {
+        let xs = tree(&[("device/vif/0/mac", "00:16:3e:aa:bb:01"),
+                        ("device/vif/1/mac", "00:16:3e:aa:bb:01"),
+                        ("device/vif/2/mac", "00:16:3e:aa:bb:02")]);
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:01"), None);
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:02"), Some(2));
+    }
+
+    #[test]
+    fn stacked_interfaces() {
+        // never looked up, they would get the VIF of the NIC below them
+        for kind in [NetInterfaceKind::Bridge, NetInterfaceKind::Bond, NetInterfaceKind::Vlan,
+                     NetInterfaceKind::Macvlan, NetInterfaceKind::Unknown] {
+            assert_eq!(get_toolstack_interface("stacked0", &kind, Some("00:16:3e:aa:bb:01")),
+                       ToolstackNetInterface::None);
+        }
+    }
+
+    #[test]
+    fn bad_vif_id() {
+        let xs = tree(&[("device/vif/x/mac", "00:16:3e:aa:bb:01"),
+                        ("device/vif/3/mac", "00:16:3e:aa:bb:01")]);
+        assert_eq!(find_vif_by_mac(&xs, "00:16:3e:aa:bb:01"), Some(3));
+    }
+}
@@ -0,0 +1,97 @@
+// Parsing of FreeBSD newbus device information, kept free of any
+// FreeBSD-specific API so it can be built and tested anywhere.
+
+// "xn0" -> ("xn", 0)
+pub fn parse_driver_name(driver_name: &str) -> Option<(&str, u32)> {
+    let unit_start = driver_name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (driver, unit) = driver_name.split_at(unit_start);
+    if driver.is_empty() {
+        return None;
+    }
+    Some((driver, unit.parse().ok()?))
+}
+
+// Extract the VIF id from the Xenstore node found in the
+// space-separated "key=value" pairs of `dev.xn.N.%pnpinfo` or
+// `dev.xn.N.%location`.  Returns None for devices not attached to a
+// VIF node.
+pub fn vif_id_from_devinfo(devinfo: &str) -> Option<u32> {
+    frontend_id_from_devinfo(devinfo, "vif")
+}
+
+// same for VBDs, from `dev.xbd.N.*`
+pub fn vbd_id_from_devinfo(devinfo: &str) -> Option<u32> {
+    frontend_id_from_devinfo(devinfo, "vbd")
+}
+
+fn frontend_id_from_devinfo(devinfo: &str, device_class: &str) -> Option<u32> {
+    let prefix = format!("device/{device_class}/");
+    for pair in devinfo.split_whitespace() {
+        let Some((_key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let Some(frontend_id) = value.trim_matches('"').strip_prefix(&prefix) else {
+            continue;
+        };
+        match frontend_id.parse() {
+            Ok(frontend_id) => return Some(frontend_id),
+            Err(e) => {
+                log::error!("cannot parse an id after {prefix} in {devinfo:?}: {e}");
+                return None;
+            },
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn driver_name() {
+        assert_eq!(parse_driver_name("xn0"), Some(("xn", 0)));
+        assert_eq!(parse_driver_name("xn12"), Some(("xn", 12)));
+        assert_eq!(parse_driver_name("vtnet1"), Some(("vtnet", 1)));
+        // digits inside the driver name
+        assert_eq!(parse_driver_name("mlx5en3"), Some(("mlx5en", 3)));
+        assert_eq!(parse_driver_name("xn"), None);
+        assert_eq!(parse_driver_name("0"), None);
+        assert_eq!(parse_driver_name(""), None);
+    }
+
+    #[test]
+    fn vif_id() {
+        // quoted, as found in dev.xn.N.%pnpinfo
+        let pnpinfo = r#"xenstore_path="device/vif/3" xenbus_dev_type="vif" xenbus_peer_domid="0""#;
+        assert_eq!(vif_id_from_devinfo(pnpinfo), Some(3));
+        assert_eq!(vif_id_from_devinfo("xenstore_path=device/vif/0"), Some(0));
+        // the node, not the (possibly renamed) interface, tells
+        assert_eq!(vif_id_from_devinfo(r#"xenstore_path="device/vif/7" ifname="lan0""#), Some(7));
+        assert_eq!(vif_id_from_devinfo(""), None);
+    }
+
+    #[test]
+    fn not_a_vif() {
+        // an xn device attached to something else than a VIF node
+        assert_eq!(vif_id_from_devinfo(r#"xenstore_path="device/vbd/768""#), None);
+        assert_eq!(vif_id_from_devinfo("slot=1 function=0 dbsf=pci0:0:1:0"), None);
+        assert_eq!(vbd_id_from_devinfo(r#"xenstore_path="device/vif/0""#), None);
+    }
+
+    #[test]
+    fn vbd_id() {
+        let pnpinfo = r#"xenstore_path="device/vbd/51712" xenbus_dev_type="vbd""#;
+        assert_eq!(vbd_id_from_devinfo(pnpinfo), Some(51712));
+        assert_eq!(vbd_id_from_devinfo("xenstore_path=device/vbd/768"), Some(768));
+    }
+
+    #[test]
+    fn malformed_id() {
+        assert_eq!(frontend_id_from_devinfo(r#"xenstore_path="device/vif/""#, "vif"), None);
+        assert_eq!(frontend_id_from_devinfo(r#"xenstore_path="device/vif/x1""#, "vif"), None);
+        assert_eq!(frontend_id_from_devinfo(r#"xenstore_path="device/vif/-1""#, "vif"), None);
+        assert_eq!(frontend_id_from_devinfo("xenstore_path", "vif"), None);
+        assert_eq!(frontend_id_from_devinfo(r#"xenstore_path="device/vif/2""#, "vif"), Some(2));
+    }
+}
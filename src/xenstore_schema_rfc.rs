@@ -1,4 +1,6 @@
-use crate::datastructs::{KernelInfo, NetEvent, NetEventOp};
+use crate::datastructs::{BalloonInfo, BlockInfo, CollectorHealth, DnsInfo, FsInfo, HostnameInfo,
+                         KernelInfo, MemInfo, MountEvent, MountEventOp, NetEvent, NetEventOp,
+                         NetRoute, OomInfo, PressureInfo, PsiAverages, ToolstackNetInterface};
 use crate::publisher::{xs_publish, xs_unpublish, XenstoreSchema};
 use std::io;
 use std::net::IpAddr;
@@ -6,6 +8,13 @@ use xenstore_rs::Xs;
 
 pub struct Schema {
     xs: Xs,
+    // keys of swap devices in Xenstore, to remove vanished ones
+    swap_devices: Vec<String>,
+    // same for filesystems, None until first published
+    filesystems: Option<Vec<String>>,
+    disks: Option<Vec<String>>,
+    // whether entries from a previous run were removed
+    mounts_cleaned: bool,
 }
 
 const PROTOCOL_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -14,7 +23,16 @@ const PROTOCOL_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 impl Schema {
     pub fn new(xs: Xs) -> Box<dyn XenstoreSchema> {
-        Box::new(Schema { xs })
+        Box::new(Schema { xs, swap_devices: vec![], filesystems: None,
+                          disks: None, mounts_cleaned: false })
+    }
+
+    fn publish_psi_averages(&self, prefix: &str, averages: &PsiAverages) -> io::Result<()> {
+        xs_publish(&self.xs, &format!("{prefix}/avg10"), &format!("{:.2}", averages.avg10))?;
+        xs_publish(&self.xs, &format!("{prefix}/avg60"), &format!("{:.2}", averages.avg60))?;
+        xs_publish(&self.xs, &format!("{prefix}/avg300"), &format!("{:.2}", averages.avg300))?;
+        xs_publish(&self.xs, &format!("{prefix}/total"), &averages.total_us.to_string())?;
+        Ok(())
     }
 }
 
@@ -39,18 +57,223 @@ impl XenstoreSchema for Schema {
         xs_unpublish(&self.xs, "data/net")
     }
 
-    fn publish_memfree(&self, _mem_free_kb: usize) -> io::Result<()> {
-        //xs_publish(&self.xs, "data/meminfo_free", &mem_free_kb.to_string())?;
+    fn publish_health(&mut self, collector: &str, health: CollectorHealth) -> io::Result<()> {
+        let prefix = format!("data/health/{collector}");
+        match health {
+            CollectorHealth::Ok => {
+                xs_publish(&self.xs, &format!("{prefix}/state"), "ok")?;
+                xs_unpublish(&self.xs, &format!("{prefix}/failures"))?;
+            },
+            CollectorHealth::Failing(failures) => {
+                xs_publish(&self.xs, &format!("{prefix}/state"), "failing")?;
+                xs_publish(&self.xs, &format!("{prefix}/failures"), &failures.to_string())?;
+            },
+        }
+        Ok(())
+    }
+
+    fn publish_meminfo(&mut self, meminfo: &MemInfo) -> io::Result<()> {
+        for (name, value) in meminfo.fields() {
+            xs_publish(&self.xs, &format!("data/memory/{name}"), &value.to_string())?;
+        }
+        let swap_devices: Vec<String> = meminfo.swap_devices.iter()
+            .map(|device| munged_name(device.name.strip_prefix("/dev/").unwrap_or(&device.name)))
+            .collect();
+        for key in &self.swap_devices {
+            if !swap_devices.contains(key) {
+                xs_unpublish(&self.xs, &format!("data/memory/swap/device/{key}"))?;
+            }
+        }
+        for (key, device) in swap_devices.iter().zip(meminfo.swap_devices.iter()) {
+            let prefix = format!("data/memory/swap/device/{key}");
+            xs_publish(&self.xs, &prefix, &device.name)?;
+            xs_publish(&self.xs, &format!("{prefix}/kind"), &device.kind)?;
+            xs_publish(&self.xs, &format!("{prefix}/size"), &device.size_kb.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/used"), &device.used_kb.to_string())?;
+            if let Some(priority) = device.priority {
+                xs_publish(&self.xs, &format!("{prefix}/priority"), &priority.to_string())?;
+            }
+        }
+        self.swap_devices = swap_devices;
+        Ok(())
+    }
+
+    fn publish_balloon(&mut self, balloon: &BalloonInfo) -> io::Result<()> {
+        xs_publish(&self.xs, "data/memory/balloon/current", &balloon.current_kb.to_string())?;
+        xs_publish(&self.xs, "data/memory/balloon/target", &balloon.target_kb.to_string())?;
+        xs_publish(&self.xs, "data/memory/balloon/state", balloon.state.as_str())?;
+        Ok(())
+    }
+
+    fn publish_pressure(&self, pressure: &PressureInfo) -> io::Result<()> {
+        for (resource, info) in &pressure.resources {
+            let prefix = format!("data/pressure/{}", resource.as_str());
+            self.publish_psi_averages(&format!("{prefix}/some"), &info.some)?;
+            if let Some(full) = &info.full {
+                self.publish_psi_averages(&format!("{prefix}/full"), full)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_oom(&self, oom: &OomInfo) -> io::Result<()> {
+        xs_publish(&self.xs, "data/memory/oom/kills", &oom.kills.to_string())?;
+        if let Some(last_kill) = &oom.last_kill {
+            xs_publish(&self.xs, "data/memory/oom/last/time", &last_kill.time.to_string())?;
+            xs_publish(&self.xs, "data/memory/oom/last/pid", &last_kill.pid.to_string())?;
+            xs_publish(&self.xs, "data/memory/oom/last/process", &last_kill.process)?;
+            match &last_kill.cgroup {
+                Some(cgroup) => xs_publish(&self.xs, "data/memory/oom/last/cgroup", cgroup)?,
+                None => xs_unpublish(&self.xs, "data/memory/oom/last/cgroup")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_fs(&mut self, fs_info: &FsInfo) -> io::Result<()> {
+        let filesystems: Vec<String> = fs_info.filesystems.iter()
+            .map(|fs| munged_name(&fs.mountpoint))
+            .collect();
+        match &self.filesystems {
+            // entries from a previous run
+            None => xs_unpublish(&self.xs, "data/fs")?,
+            Some(published) => {
+                for key in published {
+                    if !filesystems.contains(key) {
+                        xs_unpublish(&self.xs, &format!("data/fs/{key}"))?;
+                    }
+                }
+            },
+        }
+        for (key, fs) in filesystems.iter().zip(fs_info.filesystems.iter()) {
+            let prefix = format!("data/fs/{key}");
+            xs_publish(&self.xs, &prefix, &fs.mountpoint)?;
+            xs_publish(&self.xs, &format!("{prefix}/device"), &fs.device)?;
+            match fs.vbd {
+                Some(vbd_id) => xs_publish(&self.xs, &format!("{prefix}/vbd"),
+                                           &vbd_id.to_string())?,
+                None => xs_unpublish(&self.xs, &format!("{prefix}/vbd"))?,
+            }
+            xs_publish(&self.xs, &format!("{prefix}/type"), &fs.fstype)?;
+            xs_publish(&self.xs, &format!("{prefix}/size"), &fs.size_kb.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/used"), &fs.used_kb.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/available"), &fs.available_kb.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/inodes/total"),
+                       &fs.inodes_total.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/inodes/free"), &fs.inodes_free.to_string())?;
+        }
+        self.filesystems = Some(filesystems);
+        Ok(())
+    }
+
+    fn publish_mount_event(&mut self, event: &MountEvent) -> io::Result<()> {
+        if !self.mounts_cleaned {
+            xs_unpublish(&self.xs, "data/mounts")?;
+            self.mounts_cleaned = true;
+        }
+        let mount = &event.mount;
+        let prefix = format!("data/mounts/{}", munged_name(&mount.mountpoint));
+        match event.op {
+            MountEventOp::Unmounted => xs_unpublish(&self.xs, &prefix)?,
+            MountEventOp::Mounted | MountEventOp::Changed => {
+                xs_publish(&self.xs, &prefix, &mount.mountpoint)?;
+                xs_publish(&self.xs, &format!("{prefix}/device"), &mount.device)?;
+                xs_publish(&self.xs, &format!("{prefix}/type"), &mount.fstype)?;
+                xs_publish(&self.xs, &format!("{prefix}/options"), &mount.options)?;
+                xs_publish(&self.xs, &format!("{prefix}/read-only"),
+                           if mount.read_only { "1" } else { "0" })?;
+            },
+        }
+        Ok(())
+    }
+
+    fn publish_block(&mut self, block_info: &BlockInfo) -> io::Result<()> {
+        // keyed by VBD id when known, as dom0 knows them this way
+        let disks: Vec<String> = block_info.disks.iter()
+            .map(|disk| match disk.vbd {
+                Some(vbd_id) => vbd_id.to_string(),
+                None => munged_name(&disk.name),
+            })
+            .collect();
+        match &self.disks {
+            // entries from a previous run
+            None => xs_unpublish(&self.xs, "data/block")?,
+            Some(published) => {
+                for key in published {
+                    if !disks.contains(key) {
+                        xs_unpublish(&self.xs, &format!("data/block/{key}"))?;
+                    }
+                }
+            },
+        }
+        for (key, disk) in disks.iter().zip(block_info.disks.iter()) {
+            let prefix = format!("data/block/{key}");
+            xs_publish(&self.xs, &prefix, &disk.name)?;
+            xs_publish(&self.xs, &format!("{prefix}/read/ops"), &disk.read_ops.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/read/bytes"), &disk.read_bytes.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/write/ops"), &disk.write_ops.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/write/bytes"),
+                       &disk.write_bytes.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/in-flight"), &disk.in_flight.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/busy-time"), &disk.busy_ms.to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/queue-time"), &disk.queue_ms.to_string())?;
+        }
+        self.disks = Some(disks);
+        Ok(())
+    }
+
+    fn publish_hostname(&mut self, hostname_info: &HostnameInfo) -> io::Result<()> {
+        xs_publish(&self.xs, "data/host/name", &hostname_info.hostname)?;
+        match &hostname_info.fqdn {
+            Some(fqdn) => xs_publish(&self.xs, "data/host/fqdn", fqdn)?,
+            None => xs_unpublish(&self.xs, "data/host/fqdn")?,
+        }
+        Ok(())
+    }
+
+    fn publish_dns(&mut self, dns_info: &DnsInfo) -> io::Result<()> {
+        // lists are ordered, we cannot avoid indices here; drop
+        // previous entries so no stale ones remain
+        xs_unpublish(&self.xs, "data/dns")?;
+        for (idx, nameserver) in dns_info.nameservers.iter().enumerate() {
+            xs_publish(&self.xs, &format!("data/dns/nameserver/{idx}"), nameserver)?;
+        }
+        for (idx, domain) in dns_info.search.iter().enumerate() {
+            xs_publish(&self.xs, &format!("data/dns/search/{idx}"), domain)?;
+        }
         Ok(())
     }
 
     #[allow(clippy::useless_format)]
     fn publish_netevent(&mut self, event: &NetEvent) -> io::Result<()> {
         let iface_id = &event.iface.borrow().index;
-        let xs_iface_prefix = format!("data/net/{iface_id}");
+        let xs_iface_prefix = match &event.iface.borrow().netns {
+            // indices are only unique within a namespace
+            Some(netns) => format!("data/net/{iface_id}@{}", munged_name(netns)),
+            None => format!("data/net/{iface_id}"),
+        };
         match &event.op {
             NetEventOp::AddIface => {
                 xs_publish(&self.xs, &format!("{xs_iface_prefix}"), &event.iface.borrow().name)?;
+                if let Some(netns) = &event.iface.borrow().netns {
+                    xs_publish(&self.xs, &format!("{xs_iface_prefix}/netns"), netns)?;
+                }
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/kind"),
+                           event.iface.borrow().kind.as_str())?;
+                // order is significant, and names are not valid keys
+                xs_unpublish(&self.xs, &format!("{xs_iface_prefix}/altname"))?;
+                for (idx, altname) in event.iface.borrow().altnames.iter().enumerate() {
+                    xs_publish(&self.xs, &format!("{xs_iface_prefix}/altname/{idx}"), altname)?;
+                }
+                match &event.iface.borrow().perm_mac {
+                    Some(perm_mac) =>
+                        xs_publish(&self.xs, &format!("{xs_iface_prefix}/perm-mac"), perm_mac)?,
+                    None => xs_unpublish(&self.xs, &format!("{xs_iface_prefix}/perm-mac"))?,
+                }
+                // allows matching with the backend side of the VIF
+                if let ToolstackNetInterface::Vif(vif_id) = event.iface.borrow().toolstack_iface {
+                    xs_publish(&self.xs, &format!("{xs_iface_prefix}/vif"), &vif_id.to_string())?;
+                }
             },
             NetEventOp::RmIface => {
                 xs_unpublish(&self.xs, &format!("{xs_iface_prefix}"))?;
@@ -69,6 +292,29 @@ impl XenstoreSchema for Schema {
             NetEventOp::RmMac(_) => {
                 xs_unpublish(&self.xs, &format!("{xs_iface_prefix}"))?;
             },
+            NetEventOp::LinkState(state) => {
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/link/admin"),
+                           if state.admin_up { "up" } else { "down" })?;
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/link/oper"),
+                           state.oper.as_str())?;
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/link/carrier"),
+                           if state.carrier { "1" } else { "0" })?;
+            },
+            NetEventOp::AddRoute(route) => {
+                let key_suffix = munged_route(route);
+                let gateway = route.gateway.map(|gw| gw.to_string()).unwrap_or_default();
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/{key_suffix}"), &gateway)?;
+            },
+            NetEventOp::RmRoute(route) => {
+                let key_suffix = munged_route(route);
+                xs_unpublish(&self.xs, &format!("{xs_iface_prefix}/{key_suffix}"))?;
+            },
+            NetEventOp::Stats(stats) => {
+                for (direction, counter, value) in stats.counters() {
+                    xs_publish(&self.xs, &format!("{xs_iface_prefix}/stats/{direction}/{counter}"),
+                               &value.to_string())?;
+                }
+            },
         }
         Ok(())
     }
@@ -82,3 +328,38 @@ fn munged_address(addr: &IpAddr) -> String {
             "ipv6/".to_string() + &addr.to_string().replace(':', "_"),
     }
 }
+
+// keep only characters valid in a Xenstore path component, others
+// (including "_" itself, so names cannot collide) becoming "_" and
+// the hex code of each of their bytes, e.g. "/var/log" -> "_2fvar_2flog"
+fn munged_name(name: &str) -> String {
+    let mut munged = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            munged.push(byte as char);
+        } else {
+            munged.push_str(&format!("_{byte:02x}"));
+        }
+    }
+    munged
+}
+
+// eg. "route/ipv4/0_0_0_0-0" for the IPv4 default route
+fn munged_route(route: &NetRoute) -> String {
+    format!("route/{}-{}", munged_address(&route.destination), route.prefix_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn munged_names() {
+        assert_eq!(munged_name("xvda-1"), "xvda-1");
+        assert_eq!(munged_name("/var/log"), "_2fvar_2flog");
+        assert_eq!(munged_name("/var_log"), "_2fvar_5flog");
+        assert_eq!(munged_name("/mnt/a b"), "_2fmnt_2fa_20b");
+        assert_eq!(munged_name("/é"), "_2f_c3_a9");
+        assert_eq!(munged_name(""), "");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::datastructs::{KernelInfo, NetEvent, NetEventOp, NetInterface, ToolstackNetInterface};
+use crate::datastructs::{BalloonInfo, BalloonState, BlockInfo, CollectorHealth, DnsInfo, FsInfo, HostnameInfo, KernelInfo, MemInfo, MountEvent, NetEvent, NetEventOp, NetInterface, NetInterfaceKey, OomInfo, PressureInfo, ToolstackNetInterface};
 use crate::publisher::{xs_publish, xs_unpublish, XenstoreSchema};
 use std::collections::HashMap;
 use std::io;
@@ -15,6 +15,11 @@ pub struct Schema {
     // with domain ownership.  OTOH libxl creates it readonly, so we
     // catch the case where it is so to avoid uselessly retrying.
     forbidden_control_feature_balloon: bool,
+    // whether we already advertise it
+    feature_balloon: bool,
+
+    // mountpoints by volume index, which cannot change while mounted
+    volumes: Vec<Option<String>>,
 }
 
 const NUM_IFACE_IPS: usize = 10;
@@ -23,7 +28,7 @@ struct IfaceIpStruct {
     v4: IfaceIpList,
     v6: IfaceIpList,
 }
-type IpList = HashMap<u32, IfaceIpStruct>;
+type IpList = HashMap<NetInterfaceKey, IfaceIpStruct>;
 
 // pseudo version for xe-daemon compatibility, real agent version in
 // BuildVersion below
@@ -35,7 +40,9 @@ impl Schema {
     pub fn new(xs: Xs) -> Box<dyn XenstoreSchema> {
         let ip_addresses = IpList::new();
         Box::new(Schema { xs, ip_addresses,
-                          forbidden_control_feature_balloon: false})
+                          forbidden_control_feature_balloon: false,
+                          feature_balloon: false,
+                          volumes: vec![]})
     }
 }
 
@@ -76,28 +83,114 @@ impl XenstoreSchema for Schema {
             xs_publish(&self.xs, "data/meminfo_total", &mem_total_kb.to_string())?;
         }
 
-        if !self.forbidden_control_feature_balloon {
-            // we may want to be more clever some day, e.g. by
-            // checking if the guest indeed has ballooning, and if the
-            // balloon driver has reached the requested initial
-            // `~/memory/target` value (or, possibly, to rely on the
-            // balloon driver to do the job of signaling this
-            // condition)
-            match xs_publish(&self.xs, "control/feature-balloon", "1") {
-                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
-                    log::warn!("cannot write control/feature-balloon (impacts XAPI's squeezed)");
-                    self.forbidden_control_feature_balloon = true;
-                },
-                Ok(_) => (),
-                e => return e,
+        Ok(())
+    }
+
+    // advertised as long as there is a balloon driver, even when it
+    // cannot reach its target: squeezed would otherwise stop
+    // ballooning the guest entirely
+    fn publish_balloon(&mut self, balloon: &BalloonInfo) -> io::Result<()> {
+        if balloon.state == BalloonState::Stuck {
+            log::warn!("balloon stuck at {} KiB for a target of {} KiB",
+                       balloon.current_kb, balloon.target_kb);
+        }
+        // not part of the xenstore-paths doc, and not read by squeezed
+        // which spots stuck guests on its own: for humans and other tools
+        xs_publish(&self.xs, "data/balloon_state", balloon.state.as_str())?;
+        if self.forbidden_control_feature_balloon || self.feature_balloon {
+            return Ok(());
+        }
+        match xs_publish(&self.xs, "control/feature-balloon", "1") {
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                log::warn!("cannot write control/feature-balloon (impacts XAPI's squeezed)");
+                self.forbidden_control_feature_balloon = true;
+            },
+            Ok(_) => self.feature_balloon = true,
+            e => return e,
+        }
+        Ok(())
+    }
+
+    // no XAPI equivalent
+    fn publish_health(&mut self, _collector: &str, _health: CollectorHealth) -> io::Result<()> {
+        Ok(())
+    }
+
+    // no XAPI equivalent
+    fn publish_pressure(&self, _pressure: &PressureInfo) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn publish_oom(&self, _oom: &OomInfo) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn publish_block(&mut self, _block_info: &BlockInfo) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn publish_mount_event(&mut self, _event: &MountEvent) -> io::Result<()> {
+        Ok(())
+    }
+
+    // same layout as the Windows PV tools, with sizes in bytes
+    fn publish_fs(&mut self, fs_info: &FsInfo) -> io::Result<()> {
+        if self.volumes.iter().all(Option::is_none) {
+            // entries from a previous run
+            xs_unpublish(&self.xs, "data/volumes")?;
+        }
+        for (index, volume) in self.volumes.iter_mut().enumerate() {
+            if let Some(mountpoint) = volume {
+                if !fs_info.filesystems.iter().any(|fs| &fs.mountpoint == mountpoint) {
+                    xs_unpublish(&self.xs, &format!("data/volumes/{index}"))?;
+                    *volume = None;
+                }
             }
         }
+        for fs in &fs_info.filesystems {
+            let index = match self.volumes.iter().position(
+                |volume| volume.as_ref() == Some(&fs.mountpoint)) {
+                Some(index) => index,
+                None => {
+                    let index = match self.volumes.iter().position(Option::is_none) {
+                        Some(index) => index,
+                        None => {
+                            self.volumes.push(None);
+                            self.volumes.len() - 1
+                        },
+                    };
+                    self.volumes[index] = Some(fs.mountpoint.clone());
+                    index
+                },
+            };
+            let prefix = format!("data/volumes/{index}");
+            xs_publish(&self.xs, &format!("{prefix}/name"), &fs.device)?;
+            xs_publish(&self.xs, &format!("{prefix}/filesystem"), &fs.fstype)?;
+            xs_publish(&self.xs, &format!("{prefix}/size"), &(fs.size_kb * 1024).to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/free"),
+                       &(fs.available_kb * 1024).to_string())?;
+            xs_publish(&self.xs, &format!("{prefix}/mount_points/0"), &fs.mountpoint)?;
+        }
+        Ok(())
+    }
 
+    fn publish_meminfo(&mut self, meminfo: &MemInfo) -> io::Result<()> {
+        xs_publish(&self.xs, "data/meminfo_free", &meminfo.available_kb.to_string())?;
         Ok(())
     }
 
-    fn publish_memfree(&self, mem_free_kb: usize) -> io::Result<()> {
-        xs_publish(&self.xs, "data/meminfo_free", &mem_free_kb.to_string())?;
+    // same keys as the Windows PV tools
+    fn publish_hostname(&mut self, hostname_info: &HostnameInfo) -> io::Result<()> {
+        xs_publish(&self.xs, "data/host_name", &hostname_info.hostname)?;
+        match &hostname_info.fqdn {
+            Some(fqdn) => xs_publish(&self.xs, "data/host_name_dns", fqdn)?,
+            None => xs_unpublish(&self.xs, "data/host_name_dns")?,
+        }
+        Ok(())
+    }
+
+    fn publish_dns(&mut self, _dns_info: &DnsInfo) -> io::Result<()> {
+        // no such info in XAPI
         Ok(())
     }
 
@@ -134,6 +227,35 @@ impl XenstoreSchema for Schema {
             NetEventOp::RmMac(_mac_address) => {
                 log::debug!("RmMac not applied");
             },
+
+            // not part of the xenstore-paths doc, lets dom0 notice a
+            // VIF downed from within the guest
+            NetEventOp::LinkState(state) => {
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/admin-state"),
+                           if state.admin_up { "up" } else { "down" })?;
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/oper-state"),
+                           state.oper.as_str())?;
+            },
+
+            // routes are not exposed, but XAPI takes slot 0 as "the"
+            // address of a VIF, so we make sure the preferred source
+            // address of the default route goes there
+            NetEventOp::AddRoute(route) => {
+                if let (true, Some(source)) = (route.is_default(), route.source) {
+                    self.promote_address(&source, &event.iface.borrow(), &xs_iface_prefix)?;
+                }
+            },
+            NetEventOp::RmRoute(_route) => {
+                log::debug!("RmRoute not applied");
+            },
+
+            // keyed by VIF id, for comparison with the backend counters
+            NetEventOp::Stats(stats) => {
+                for (direction, counter, value) in stats.counters() {
+                    xs_publish(&self.xs, &format!("{xs_iface_prefix}/stats/{direction}_{counter}"),
+                               &value.to_string())?;
+                }
+            },
         }
         Ok(())
     }
@@ -147,7 +269,7 @@ impl XenstoreSchema for Schema {
 impl Schema {
     fn munged_address(&mut self, addr: &IpAddr, iface: &NetInterface) -> io::Result<String> {
         let ip_entry = self.ip_addresses
-            .entry(iface.index)
+            .entry(iface.key())
             .or_insert(IfaceIpStruct{v4: [None; NUM_IFACE_IPS], v6: [None; NUM_IFACE_IPS]});
         let ip_list = match addr { IpAddr::V4(_) => &mut ip_entry.v4,
                                    IpAddr::V6(_) => &mut ip_entry.v6 };
@@ -157,6 +279,33 @@ impl Schema {
             IpAddr::V6(_) => Ok(format!("ipv6/{ip_slot}")),
         }
     }
+
+    // move a known address to slot 0, swapping with its previous occupant
+    fn promote_address(&mut self, addr: &IpAddr, iface: &NetInterface, xs_iface_prefix: &str)
+                       -> io::Result<()> {
+        let Some(ip_entry) = self.ip_addresses.get_mut(&iface.key()) else {
+            return Ok(());
+        };
+        let (ip_list, family) = match addr { IpAddr::V4(_) => (&mut ip_entry.v4, "ipv4"),
+                                             IpAddr::V6(_) => (&mut ip_entry.v6, "ipv6") };
+        let Some(idx) = ip_list.iter().position(|item| *item == Some(*addr)) else {
+            log::debug!("default route source {addr} not a known address of {}", iface.name);
+            return Ok(());
+        };
+        if idx == 0 {
+            return Ok(());
+        }
+        ip_list.swap(0, idx);
+        xs_publish(&self.xs, &format!("{xs_iface_prefix}/{family}/0"), &addr.to_string())?;
+        match ip_list[idx] {
+            Some(other) =>
+                xs_publish(&self.xs, &format!("{xs_iface_prefix}/{family}/{idx}"),
+                           &other.to_string())?,
+            None =>
+                xs_unpublish(&self.xs, &format!("{xs_iface_prefix}/{family}/{idx}"))?,
+        }
+        Ok(())
+    }
 }
 
 fn get_ip_slot(ip: &IpAddr, list: &mut IfaceIpList) -> io::Result<usize> {